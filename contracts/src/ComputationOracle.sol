@@ -7,6 +7,12 @@ import {ISP1Verifier} from "@sp1-contracts/ISP1Verifier.sol";
 contract ComputationOracle {
     using MerkleProof for bytes32[];
 
+    // Must match `ExecutionStatus` committed by the prover program
+    enum ExecutionStatus {
+        Success,
+        Revert
+    }
+
     mapping(bytes32 merkleRoot => bool proved) public proved;
 
     address governance;
@@ -28,7 +34,12 @@ contract ComputationOracle {
     }
 
     function verifyComputation(bytes32 codeHash, bytes32 inputHash, bytes32 outputHash, bytes32[] calldata proof) external view {
-        bytes memory computationProofPublicInput = bytes.concat(codeHash, inputHash, outputHash);
+        verifyExecution(codeHash, inputHash, ExecutionStatus.Success, outputHash, proof);
+    }
+
+    /// @notice Verifies the proven outcome of the execution, `outputHash` is the hash of the revert data if it reverted.
+    function verifyExecution(bytes32 codeHash, bytes32 inputHash, ExecutionStatus status, bytes32 outputHash, bytes32[] calldata proof) public view {
+        bytes memory computationProofPublicInput = bytes.concat(codeHash, inputHash, outputHash, bytes1(uint8(status)));
         bytes32 leaf = keccak256(computationProofPublicInput);
         require(proved[proof.processProof(leaf)]);
     }
@@ -0,0 +1,70 @@
+// SPDX-License-Identifier: UNLICENSED
+pragma solidity ^0.8.13;
+
+import {Test} from "forge-std/Test.sol";
+import {stdJson} from "forge-std/StdJson.sol";
+import {ComputationOracle} from "../src/ComputationOracle.sol";
+
+/// Verifies the requests of `test/fixtures/mock-fixture.json`, generated from `test/fixtures/requests.json` with
+/// `cargo run --bin fixture -- --mock --input ../contracts/test/fixtures/requests.json --out ../contracts/test/fixtures`
+/// in the prover directory. Its root is submitted with the dummy verifier.
+contract ComputationOracleTest is Test {
+    using stdJson for string;
+
+    ComputationOracle oracle;
+    string fixture;
+
+    function setUp() public {
+        oracle = new ComputationOracle();
+        fixture = vm.readFile(string.concat(vm.projectRoot(), "/test/fixtures/mock-fixture.json"));
+        oracle.proveComputation(fixture.readBytes32(".merkleRoot"), "", true);
+    }
+
+    function request(uint256 i) internal view returns (bytes32, bytes32, ComputationOracle.ExecutionStatus, bytes32, bytes32[] memory) {
+        string memory key = string.concat(".requests[", vm.toString(i), "]");
+        return (
+            fixture.readBytes32(string.concat(key, ".codeHash")),
+            fixture.readBytes32(string.concat(key, ".inputHash")),
+            ComputationOracle.ExecutionStatus(fixture.readUint(string.concat(key, ".status"))),
+            fixture.readBytes32(string.concat(key, ".outputHash")),
+            fixture.readBytes32Array(string.concat(key, ".merkleProof"))
+        );
+    }
+
+    function requestCount() internal view returns (uint256) {
+        return fixture.readBytes32Array(".requests[*].codeHash").length;
+    }
+
+    function test_VerifyExecution() public view {
+        for (uint256 i = 0; i < requestCount(); i++) {
+            (bytes32 codeHash, bytes32 inputHash, ComputationOracle.ExecutionStatus status, bytes32 outputHash, bytes32[] memory proof) = request(i);
+            oracle.verifyExecution(codeHash, inputHash, status, outputHash, proof);
+            assertEq(outputHash, keccak256(fixture.readBytes(string.concat(".requests[", vm.toString(i), "].output"))));
+        }
+    }
+
+    function test_VerifyComputation() public view {
+        (bytes32 codeHash, bytes32 inputHash, ComputationOracle.ExecutionStatus status, bytes32 outputHash, bytes32[] memory proof) = request(0);
+        assertEq(uint8(status), uint8(ComputationOracle.ExecutionStatus.Success));
+        oracle.verifyComputation(codeHash, inputHash, outputHash, proof);
+    }
+
+    function test_RevertWhen_OutputDiffers() public {
+        (bytes32 codeHash, bytes32 inputHash, ComputationOracle.ExecutionStatus status,, bytes32[] memory proof) = request(0);
+        vm.expectRevert();
+        oracle.verifyExecution(codeHash, inputHash, status, keccak256("other output"), proof);
+    }
+
+    function test_RevertWhen_StatusDiffers() public {
+        (bytes32 codeHash, bytes32 inputHash,, bytes32 outputHash, bytes32[] memory proof) = request(2);
+        vm.expectRevert();
+        oracle.verifyExecution(codeHash, inputHash, ComputationOracle.ExecutionStatus.Success, outputHash, proof);
+    }
+
+    function test_RevertWhen_RootNotProved() public {
+        (bytes32 codeHash, bytes32 inputHash, ComputationOracle.ExecutionStatus status, bytes32 outputHash, bytes32[] memory proof) = request(1);
+        ComputationOracle other = new ComputationOracle();
+        vm.expectRevert();
+        other.verifyExecution(codeHash, inputHash, status, outputHash, proof);
+    }
+}
@@ -0,0 +1,194 @@
+//! Requests and responses of the prover server, shared by the server and `solc-zkmod-client`.
+//!
+//! Every route requires the API key in the [`API_KEY_HEADER`] header, the errors are plain text
+//! bodies with a non-2xx status.
+
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use web3::types::{Bytes, H160, H256, U256};
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct AddBytecodeRequest {
+    /// Runtime bytecode of the `@free` function contract.
+    #[schema(value_type = String, example = "0x6080...")]
+    pub bytecode: Bytes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct AddBytecodeResponse {
+    #[schema(value_type = String)]
+    pub code_hash: H256,
+}
+
+/// Transaction calling `@free` functions, simulated to discover its calls.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProofRequestData {
+    #[schema(value_type = String)]
+    pub address: H160,
+    #[schema(value_type = String)]
+    pub calldata: Bytes,
+    /// Decimal or hex string.
+    #[schema(value_type = String)]
+    pub value: U256,
+    #[schema(value_type = String)]
+    pub sender: H160,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct ProofRequest {
+    /// Chain of the transactions, the root is submitted to its oracle.
+    pub chain_id: u64,
+    pub requests: Vec<ProofRequestData>,
+    /// Notified on every status transition of the job.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webhook: Option<Webhook>,
+}
+
+/// Receives the [`JobEvent`]s of the job, signed with the secret.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct Webhook {
+    pub url: String,
+    /// Signs the deliveries, so the receiver can check they come from the server.
+    pub secret: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct ProofResponse {
+    /// Job id of the request.
+    pub id: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct WitnessOutput {
+    /// Witnesses in the order of the requested transactions.
+    pub witnesses: Vec<TransactionWitness>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct TransactionWitness {
+    /// `@free` calls of the transaction, in the order of the calls.
+    pub calls: Vec<CallWitness>,
+    /// Encoded witness, see `solc_zkmod_calldata`.
+    #[schema(value_type = String)]
+    pub witness: Bytes,
+    /// Transaction calldata with the witness appended.
+    #[schema(value_type = String)]
+    pub calldata: Bytes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct CallWitness {
+    #[schema(value_type = String)]
+    pub code_hash: H256,
+    /// 1 if the call reverted.
+    pub status: u8,
+    #[schema(value_type = String)]
+    pub output: Bytes,
+    #[schema(value_type = Vec<String>)]
+    pub merkle_proof: Vec<H256>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct CheckProofResponse {
+    /// The batch of the job is proven, `false` for an unknown id as well.
+    pub exists: bool,
+    /// `None` for an unknown id.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<JobStatus>,
+    #[serde(default)]
+    pub submission: Option<Submission>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum JobStatus {
+    Pending,
+    Proving,
+    Proven,
+    /// The root is recorded by the oracle of the job chain.
+    Submitted,
+    Failed,
+}
+
+impl JobStatus {
+    /// The status won't change anymore.
+    pub fn is_final(&self, submission: Option<&Submission>) -> bool {
+        match self {
+            JobStatus::Submitted | JobStatus::Failed => true,
+            JobStatus::Proven => submission.is_none(),
+            JobStatus::Pending | JobStatus::Proving => false,
+        }
+    }
+}
+
+/// Submission of the batch root to the oracle of a chain.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase", tag = "status")]
+pub enum Submission {
+    Pending,
+    /// The root is recorded, by the transaction if it's known.
+    #[serde(rename_all = "camelCase")]
+    Submitted {
+        #[schema(value_type = Option<String>)]
+        tx_hash: Option<H256>,
+    },
+    Failed { error: String },
+}
+
+/// `@free` function call to compare with its native execution.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateRequest {
+    /// Chain whose open batch the call would join.
+    pub chain_id: u64,
+    /// Runtime bytecode of the `@free` function contract.
+    #[schema(value_type = String)]
+    pub bytecode: Bytes,
+    /// Input of the call.
+    #[schema(value_type = String)]
+    pub calldata: Bytes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EstimateResponse {
+    /// Gas of the function executed on-chain, `None` if it exceeds the block gas limit.
+    pub native_gas: Option<u64>,
+    /// zkVM cycles of proving the call, charged to the cycle quota of a proof request.
+    pub cycles: u64,
+    /// Calls of the open batch sharing the verification, including this one.
+    pub batch_calls: u64,
+    /// Share of the proof verification gas.
+    pub verification_gas: u64,
+    /// Gas of the memory operations and the Merkle proof verification of the call.
+    pub overhead_gas: u64,
+    /// Gas of the offloaded call, `verificationGas + overheadGas`.
+    pub free_gas: u64,
+    /// The offloaded call is cheaper than the native one, always if it can't run on-chain.
+    pub worth_offloading: bool,
+}
+
+/// State of a job, sent on its status transitions to the event stream and the webhook.
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct JobEvent {
+    pub id: String,
+    pub status: JobStatus,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub submission: Option<Submission>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Witnesses of the transactions, once the job is proven.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub witnesses: Option<Vec<TransactionWitness>>,
+}
+
+impl JobEvent {
+    pub fn is_final(&self) -> bool {
+        self.status.is_final(self.submission.as_ref())
+    }
+}
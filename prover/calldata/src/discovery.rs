@@ -0,0 +1,90 @@
+//! Discovery revert of the `@free` calls.
+//!
+//! Until the witness of the `@free` call is appended and proven, the generated code
+//! reverts with the ABI-encoded custom error
+//! ```solidity
+//! error FreeCallDiscovery(bytes32 codeHash, bytes input);
+//! ```
+//! where `codeHash` is the keccak hash of the function bytecode, and `input` is the ABI-encoded
+//! arguments, the calldata to prove the function execution with.
+
+use crate::{read_word, word, WORD_BYTES};
+
+/// `FreeCallDiscovery(bytes32,bytes)` selector.
+pub const DISCOVERY_SELECTOR: [u8; 4] = [0x7d, 0x43, 0xe8, 0x1d];
+
+/// `@free` call discovered by the simulation of the transaction.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Discovery {
+    pub code_hash: [u8; 32],
+    pub input: Vec<u8>,
+}
+
+/// Encodes the revert data, the same way as `abi.encodeWithSelector` in the generated code.
+pub fn encode_discovery(discovery: &Discovery) -> Vec<u8> {
+    let padding = (WORD_BYTES - discovery.input.len() % WORD_BYTES) % WORD_BYTES;
+    [
+        &DISCOVERY_SELECTOR[..],
+        &discovery.code_hash[..],
+        &word(2 * WORD_BYTES)[..],
+        &word(discovery.input.len())[..],
+        &discovery.input[..],
+        &vec![0u8; padding][..],
+    ].concat()
+}
+
+/// Decodes the revert data, `None` if it is not a discovery revert.
+pub fn decode_discovery(revert_data: &[u8]) -> Option<Discovery> {
+    let data = revert_data.strip_prefix(&DISCOVERY_SELECTOR[..])?;
+
+    let code_hash = data.get(..WORD_BYTES)?.try_into().unwrap();
+    let input_offset = read_word(data, WORD_BYTES).ok()?;
+    let input_len = read_word(data, input_offset).ok()?;
+    let input_start = input_offset + WORD_BYTES;
+    let input = data.get(input_start..input_start.checked_add(input_len)?)?.to_vec();
+
+    Some(Discovery { code_hash, input })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        for len in [0, 1, 31, 32, 33, 100] {
+            let discovery = Discovery { code_hash: [7; 32], input: (0..len).map(|i| i as u8).collect() };
+            let encoded = encode_discovery(&discovery);
+            assert_eq!(encoded.len() % WORD_BYTES, 4);
+            assert_eq!(decode_discovery(&encoded), Some(discovery));
+        }
+    }
+
+    #[test]
+    fn golden_encoding() {
+        let encoded = encode_discovery(&Discovery { code_hash: [7; 32], input: vec![1, 2, 3] });
+        let mut expected = DISCOVERY_SELECTOR.to_vec();
+        expected.extend([7; 32]);
+        expected.extend(word(64));
+        expected.extend(word(3));
+        expected.extend([[1, 2, 3].as_slice(), &[0; 29]].concat());
+        assert_eq!(encoded, expected);
+    }
+
+    #[test]
+    fn decode_other_reverts() {
+        let encoded = encode_discovery(&Discovery { code_hash: [7; 32], input: vec![1, 2, 3] });
+        assert_eq!(decode_discovery(&[]), None);
+        assert_eq!(decode_discovery(&encoded[..4]), None);
+        assert_eq!(decode_discovery(&encoded[..encoded.len() - 30]), None);
+        // `Error(string)`
+        assert_eq!(decode_discovery(&[[0x08, 0xc3, 0x79, 0xa0].as_slice(), &encoded[4..]].concat()), None);
+
+        let mut oversized = encoded.clone();
+        oversized[4 + 64..4 + 96].copy_from_slice(&[0xff; 32]);
+        assert_eq!(decode_discovery(&oversized), None);
+        let mut offset = encoded;
+        offset[4 + 32..4 + 64].copy_from_slice(&word(usize::MAX));
+        assert_eq!(decode_discovery(&offset), None);
+    }
+}
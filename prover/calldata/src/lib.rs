@@ -0,0 +1,239 @@
+//! Calldata of transactions calling contracts with `@free` functions.
+//!
+//! The witnesses of the `@free` calls are appended after the original calldata, in the layout read
+//! by the code generated by the preprocessor:
+//! ```text
+//! entry | entry | ... | witness offset
+//! entry = code hash | input hash | status | output length | output | proof length | proof
+//! ```
+//! Every hash, length, the status and the offset are 32-byte words, the proof is a list of 32-byte
+//! hashes. The last word is the witness offset, which is the length of the original calldata,
+//! so the contract can read the witness via `calldataload(sub(calldatasize(), 32))`.
+//!
+//! Entries are in the order of the calls, a call uses the first entry with its code and input
+//! hashes, so repeated calls with the same input share the entry.
+
+pub mod discovery;
+
+const WORD_BYTES: usize = 32;
+
+/// Proven outcome of a `@free` call, as returned by the prover server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    /// Keccak hash of the function bytecode.
+    pub code_hash: [u8; 32],
+    /// Keccak hash of the ABI-encoded arguments.
+    pub input_hash: [u8; 32],
+    /// Whether the proven outcome is a revert.
+    pub reverted: bool,
+    /// Return data, or revert data if reverted.
+    pub output: Vec<u8>,
+    /// Merkle proof of the outcome in the proven batch.
+    pub proof: Vec<[u8; 32]>,
+}
+
+fn word(value: usize) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[WORD_BYTES - 8..].copy_from_slice(&(value as u64).to_be_bytes());
+    word
+}
+
+fn read_word(data: &[u8], offset: usize) -> Result<usize, String> {
+    let word = data
+        .get(offset..offset.saturating_add(WORD_BYTES))
+        .ok_or(format!("Word at {} is out of bounds", offset))?;
+    if word[..WORD_BYTES - 8].iter().any(|byte| *byte != 0) {
+        return Err(format!("Word at {} is too big", offset));
+    }
+    Ok(u64::from_be_bytes(word[WORD_BYTES - 8..].try_into().unwrap()) as usize)
+}
+
+/// Encodes the witnesses to be appended after `calldata_len` bytes of the original calldata.
+pub fn encode_witness(calldata_len: usize, witnesses: &[Witness]) -> Vec<u8> {
+    let mut encoded = vec![];
+    for witness in witnesses {
+        encoded.extend_from_slice(&witness.code_hash);
+        encoded.extend_from_slice(&witness.input_hash);
+        encoded.extend_from_slice(&word(witness.reverted as usize));
+        encoded.extend_from_slice(&word(witness.output.len()));
+        encoded.extend_from_slice(&witness.output);
+        encoded.extend_from_slice(&word(witness.proof.len()));
+        encoded.extend_from_slice(&witness.proof.concat());
+    }
+    encoded.extend_from_slice(&word(calldata_len));
+    encoded
+}
+
+/// Returns the final calldata, the original one with the witnesses appended.
+pub fn build_calldata(calldata: &[u8], witnesses: &[Witness]) -> Vec<u8> {
+    [calldata, &encode_witness(calldata.len(), witnesses)[..]].concat()
+}
+
+fn read_hash(data: &[u8], offset: usize) -> Result<[u8; 32], String> {
+    data.get(offset..offset.saturating_add(WORD_BYTES))
+        .map(|hash| hash.try_into().unwrap())
+        .ok_or(format!("Hash at {} is out of bounds", offset))
+}
+
+/// Inverse of `build_calldata`, splits the final calldata into the original one and the witnesses.
+pub fn parse_calldata(calldata: &[u8]) -> Result<(Vec<u8>, Vec<Witness>), String> {
+    if calldata.len() < WORD_BYTES {
+        return Err("Calldata is too short".to_string());
+    }
+    let end = calldata.len() - WORD_BYTES;
+    let offset = read_word(calldata, end)?;
+    if offset > end {
+        return Err(format!("Witness offset {} is out of bounds", offset));
+    }
+
+    let mut witnesses = vec![];
+    let mut entry = offset;
+    while entry < end {
+        let code_hash = read_hash(calldata, entry)?;
+        let input_hash = read_hash(calldata, entry + WORD_BYTES)?;
+        let status = read_word(calldata, entry + 2 * WORD_BYTES)?;
+        if status > 1 {
+            return Err(format!("Invalid status {}", status));
+        }
+
+        let output_len = read_word(calldata, entry + 3 * WORD_BYTES)?;
+        let output_start = entry + 4 * WORD_BYTES;
+        let output = calldata
+            .get(output_start..output_start.saturating_add(output_len))
+            .ok_or("Output is out of bounds")?
+            .to_vec();
+
+        let proof_len = read_word(calldata, output_start + output_len)?;
+        let proof_start = output_start + output_len + WORD_BYTES;
+        let proof_end = proof_len
+            .checked_mul(WORD_BYTES)
+            .and_then(|size| size.checked_add(proof_start))
+            .filter(|proof_end| *proof_end <= end)
+            .ok_or("Proof is out of bounds")?;
+        let proof = calldata[proof_start..proof_end]
+            .chunks(WORD_BYTES)
+            .map(|node| node.try_into().unwrap())
+            .collect();
+
+        witnesses.push(Witness {
+            code_hash,
+            input_hash,
+            reverted: status == 1,
+            output,
+            proof,
+        });
+        entry = proof_end;
+    }
+
+    Ok((calldata[..offset].to_vec(), witnesses))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn witness(seed: u8, reverted: bool, output_len: usize, proof_len: usize) -> Witness {
+        Witness {
+            code_hash: [seed; 32],
+            input_hash: [seed + 1; 32],
+            reverted,
+            output: (0..output_len).map(|i| i as u8 ^ seed).collect(),
+            proof: (0..proof_len).map(|i| [seed + 2 + i as u8; 32]).collect(),
+        }
+    }
+
+    fn round_trip(calldata: &[u8], witnesses: &[Witness]) {
+        let built = build_calldata(calldata, witnesses);
+        let (parsed_calldata, parsed) = parse_calldata(&built).unwrap();
+        assert_eq!(parsed_calldata, calldata);
+        assert_eq!(parsed, witnesses);
+    }
+
+    #[test]
+    fn round_trip_entries() {
+        let calldata = [0xa9, 0x05, 0x9c, 0xbb, 1, 2, 3];
+        round_trip(&calldata, &[]);
+        round_trip(&calldata, &[witness(1, false, 32, 2)]);
+        round_trip(&calldata, &[witness(1, false, 64, 0), witness(10, true, 4, 1), witness(20, false, 0, 5)]);
+        round_trip(&[], &[witness(1, false, 32, 1)]);
+    }
+
+    #[test]
+    fn round_trip_unaligned_outputs() {
+        for output_len in [1, 31, 33, 67] {
+            round_trip(&[1, 2, 3, 4], &[witness(1, true, output_len, 1), witness(5, false, output_len + 1, 3)]);
+        }
+    }
+
+    #[test]
+    fn golden_layout() {
+        let calldata = [0xaa; 4];
+        let first = witness(1, true, 3, 2);
+        let second = witness(7, false, 0, 1);
+        let built = build_calldata(&calldata, &[first.clone(), second.clone()]);
+
+        // entry + 64 status, entry + 96 output length, entry + 128 output,
+        // entry + 128 + outLen proof length, stride 160 + outLen + 32 * proofLen
+        let mut expected = calldata.to_vec();
+        expected.extend([1; 32]);
+        expected.extend([2; 32]);
+        expected.extend([[0; 31].as_slice(), &[1]].concat());
+        expected.extend([[0; 31].as_slice(), &[3]].concat());
+        expected.extend([1, 0, 3]);
+        expected.extend([[0; 31].as_slice(), &[2]].concat());
+        expected.extend([3; 32]);
+        expected.extend([4; 32]);
+        let entry = expected.len();
+        assert_eq!(entry - calldata.len(), 160 + 3 + 32 * 2);
+        expected.extend([7; 32]);
+        expected.extend([8; 32]);
+        expected.extend([0; 32]);
+        expected.extend([0; 32]);
+        expected.extend([[0; 31].as_slice(), &[1]].concat());
+        expected.extend([9; 32]);
+        expected.extend([[0; 31].as_slice(), &[4]].concat());
+        assert_eq!(built, expected);
+
+        assert_eq!(read_word(&built, calldata.len() + 96).unwrap(), first.output.len());
+        assert_eq!(read_word(&built, calldata.len() + 128 + first.output.len()).unwrap(), first.proof.len());
+        assert_eq!(read_word(&built, entry + 96).unwrap(), second.output.len());
+        assert_eq!(read_word(&built, built.len() - WORD_BYTES).unwrap(), calldata.len());
+    }
+
+    #[test]
+    fn parse_truncated() {
+        assert!(parse_calldata(&[]).is_err());
+        assert!(parse_calldata(&[0; 31]).is_err());
+
+        let built = build_calldata(&[1, 2, 3], &[witness(1, false, 40, 2)]);
+        let offset = &built[built.len() - WORD_BYTES..];
+        // The entry cut at every byte, with the offset word still at the end.
+        for len in 4..built.len() - WORD_BYTES {
+            let truncated = [&built[..len], offset].concat();
+            assert!(parse_calldata(&truncated).is_err(), "parsed an entry cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn parse_oversized_lengths() {
+        let calldata = [1, 2, 3];
+        let built = build_calldata(&calldata, &[witness(1, false, 4, 1)]);
+        let entry = calldata.len();
+        let with_word = |offset: usize, word: [u8; 32]| {
+            let mut data = built.clone();
+            data[offset..offset + WORD_BYTES].copy_from_slice(&word);
+            data
+        };
+
+        let offset = built.len() - WORD_BYTES;
+        assert!(parse_calldata(&with_word(offset, super::word(offset + 1))).is_err());
+        assert!(parse_calldata(&with_word(offset, [0xff; 32])).is_err());
+        assert!(parse_calldata(&with_word(entry + 64, super::word(2))).is_err());
+        assert!(parse_calldata(&with_word(entry + 96, super::word(1000))).is_err());
+        assert!(parse_calldata(&with_word(entry + 96, super::word(usize::MAX))).is_err());
+        assert!(parse_calldata(&with_word(entry + 96, [0xff; 32])).is_err());
+        assert!(parse_calldata(&with_word(entry + 132, super::word(2))).is_err());
+        assert!(parse_calldata(&with_word(entry + 132, super::word(usize::MAX))).is_err());
+        assert!(parse_calldata(&with_word(entry + 132, super::word(usize::MAX / WORD_BYTES + 1))).is_err());
+    }
+}
@@ -0,0 +1,126 @@
+//! Async client of the prover server.
+//!
+//! ```no_run
+//! # async fn example(request: solc_zkmod_client::ProofRequest) -> Result<(), solc_zkmod_client::Error> {
+//! use std::time::Duration;
+//! use solc_zkmod_client::Client;
+//!
+//! let client = Client::new("http://127.0.0.1:3000", "api key");
+//! let id = client.request_proof(&request).await?.id;
+//! let witnesses = client.wait_for_witness(&id, Duration::from_secs(10)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use web3::types::{Bytes, H256};
+pub use solc_zkmod_api::*;
+
+#[derive(Debug)]
+pub enum Error {
+    Http(reqwest::Error),
+    /// Non-2xx response with the error message of the server.
+    Status(StatusCode, String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "{}", err),
+            Error::Status(status, message) => write!(f, "{}: {}", status, message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    url: String,
+    api_key: String,
+}
+
+impl Client {
+    pub fn new(url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self::with_http_client(reqwest::Client::new(), url, api_key)
+    }
+
+    pub fn with_http_client(http: reqwest::Client, url: impl Into<String>, api_key: impl Into<String>) -> Self {
+        Self {
+            http,
+            url: url.into().trim_end_matches('/').to_string(),
+            api_key: api_key.into(),
+        }
+    }
+
+    /// Registers the bytecode of a `@free` function contract, returns its code hash.
+    pub async fn add_bytecode(&self, bytecode: Vec<u8>) -> Result<H256, Error> {
+        let request = AddBytecodeRequest { bytecode: Bytes(bytecode) };
+        let response: AddBytecodeResponse = self.send(self.http.post(self.route("/add-bytecode")).json(&request)).await?;
+        Ok(response.code_hash)
+    }
+
+    pub async fn request_proof(&self, request: &ProofRequest) -> Result<ProofResponse, Error> {
+        self.send(self.http.post(self.route("/request-proof")).json(request)).await
+    }
+
+    pub async fn check_proof(&self, id: &str) -> Result<CheckProofResponse, Error> {
+        self.send(self.http.get(self.route(&format!("/check-proof/{}", id)))).await
+    }
+
+    pub async fn get_witness(&self, id: &str) -> Result<WitnessOutput, Error> {
+        self.send(self.http.get(self.route(&format!("/get-witness/{}", id)))).await
+    }
+
+    /// Gas of the call executed on-chain compared to its `@free` cost.
+    pub async fn estimate(&self, request: &EstimateRequest) -> Result<EstimateResponse, Error> {
+        self.send(self.http.post(self.route("/estimate")).json(request)).await
+    }
+
+    /// Polls the job until it's proven, fails if it's unknown or its proving failed.
+    pub async fn wait_for_witness(&self, id: &str, interval: Duration) -> Result<WitnessOutput, Error> {
+        loop {
+            let check = self.check_proof(id).await?;
+            let Some(status) = check.status else {
+                return Err(Error::Status(StatusCode::NOT_FOUND, "Unknown id".to_string()));
+            };
+            // The witness route returns the proving error of a failed job.
+            if check.exists || status == JobStatus::Failed {
+                return self.get_witness(id).await;
+            }
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    /// The OpenAPI document of the server.
+    pub async fn openapi(&self) -> Result<serde_json::Value, Error> {
+        self.send(self.http.get(self.route("/openapi.json"))).await
+    }
+
+    fn route(&self, path: &str) -> String {
+        format!("{}{}", self.url, path)
+    }
+
+    async fn send<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T, Error> {
+        let response = request.header(API_KEY_HEADER, &self.api_key).send().await?;
+        Ok(error_for_status(response).await?.json().await?)
+    }
+}
+
+async fn error_for_status(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+    Err(Error::Status(status, response.text().await?))
+}
@@ -0,0 +1,106 @@
+//! Input of the prover program and its native execution, without the SP1 SDK, so the program
+//! can use it.
+
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::{keccak256, leaf_hash, run_evm, ExecutionOutcome};
+
+/// Single `@free` call, the bytecode is referenced by its keccak hash.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvingInput {
+    pub code_hash: [u8; 32],
+    pub calldata: Vec<u8>,
+}
+
+/// The prover program input, every unique bytecode is read and hashed once per batch.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProvingBatch {
+    pub bytecodes: Vec<Vec<u8>>,
+    pub requests: Vec<ProvingInput>,
+}
+
+impl ProvingBatch {
+    /// Appends the requests of `other`, returns the index of its first request in this batch.
+    pub fn append(&mut self, other: &ProvingBatch) -> usize {
+        let offset = self.requests.len();
+        for bytecode in &other.bytecodes {
+            if !self.bytecodes.contains(bytecode) {
+                self.bytecodes.push(bytecode.clone());
+            }
+        }
+        self.requests.extend(other.requests.iter().cloned());
+        offset
+    }
+
+    pub fn push(&mut self, bytecode: &[u8], calldata: Vec<u8>) {
+        let code_hash = keccak256(bytecode);
+        if !self.requests.iter().any(|request| request.code_hash == code_hash) {
+            self.bytecodes.push(bytecode.to_vec());
+        }
+        self.requests.push(ProvingInput { code_hash, calldata });
+    }
+
+    ///
+    /// Executes the requests the same way the prover program does.
+    /// Returns the leaf and the outcome of every request, `None` if the request can't be proven.
+    ///
+    pub fn execute(&self) -> Vec<Option<([u8; 32], ExecutionOutcome)>> {
+        // Each unique bytecode is hashed once, requests reference it by the hash.
+        let bytecodes: HashMap<[u8; 32], &Vec<u8>> = self.bytecodes
+            .iter()
+            .map(|bytecode| (keccak256(bytecode.as_slice()), bytecode))
+            .collect();
+
+        self.requests.iter().map(|request| {
+            let bytecode = bytecodes.get(&request.code_hash)?;
+            // Reverts are committed as well, only executions depending on the state are skipped.
+            let outcome = run_evm(bytecode.as_slice(), request.calldata.as_slice()).ok()?;
+            let leaf = leaf_hash(request.code_hash, request.calldata.as_slice(), &outcome);
+            Some((leaf, outcome))
+        }).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExecutionStatus;
+
+    /// CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN
+    const ECHO: [u8; 7] = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+    /// PUSH0 PUSH0 REVERT
+    const REVERT: [u8; 3] = [0x5f, 0x5f, 0xfd];
+
+    #[test]
+    fn unique_bytecodes() {
+        let mut batch = ProvingBatch::default();
+        batch.push(&ECHO, vec![1]);
+        batch.push(&ECHO, vec![2]);
+        assert_eq!(batch.bytecodes, vec![ECHO.to_vec()]);
+
+        let mut other = ProvingBatch::default();
+        other.push(&REVERT, vec![]);
+        other.push(&ECHO, vec![3]);
+        assert_eq!(batch.append(&other), 2);
+        assert_eq!(batch.bytecodes, vec![ECHO.to_vec(), REVERT.to_vec()]);
+        assert_eq!(batch.requests.len(), 4);
+        assert_eq!(batch.requests[3].code_hash, keccak256(&ECHO));
+    }
+
+    #[test]
+    fn execute_requests() {
+        let mut batch = ProvingBatch::default();
+        batch.push(&ECHO, vec![1, 2]);
+        batch.push(&REVERT, vec![]);
+        // The bytecode of the request is missing, it can't be proven.
+        batch.requests.push(ProvingInput { code_hash: keccak256(&[0x00]), calldata: vec![] });
+
+        let executed = batch.execute();
+        assert_eq!(executed.len(), 3);
+        let (leaf, outcome) = executed[0].clone().unwrap();
+        assert_eq!(outcome, ExecutionOutcome { status: ExecutionStatus::Success, output: vec![1, 2] });
+        assert_eq!(leaf, leaf_hash(keccak256(&ECHO), &[1, 2], &outcome));
+        assert_eq!(executed[1].as_ref().unwrap().1.status, ExecutionStatus::Revert);
+        assert!(executed[2].is_none());
+    }
+}
@@ -0,0 +1,88 @@
+//! Cost of a `@free` call compared to executing the function on-chain.
+
+use serde::{Deserialize, Serialize};
+use crate::evm::context::Context;
+use crate::evm::gas::execution_gas;
+use crate::batch::ProvingBatch;
+use crate::prover::prover::Prover;
+use crate::{run_evm_bounded, MAX_STEPS};
+
+/// Gas of the SP1 proof verification by the oracle, shared by the calls of a batch.
+pub const VERIFICATION_GAS: u64 = 250_000;
+/// Gas added by every `@free` call: the memory operations and the Merkle proof verification.
+pub const CALL_OVERHEAD_GAS: u64 = 15_000;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CostEstimate {
+    /// Gas of the function executed on-chain, `None` if it needs more than
+    /// [`MAX_GAS`](crate::evm::gas::MAX_GAS).
+    pub native_gas: Option<u64>,
+    /// zkVM cycles of proving the call alone.
+    pub cycles: u64,
+    /// Calls sharing the verification, including this one.
+    pub batch_calls: u64,
+    /// Share of the verification gas of the call.
+    pub verification_gas: u64,
+    pub overhead_gas: u64,
+    /// Gas of the call offloaded with `@free`, the verification share and the overhead.
+    pub free_gas: u64,
+    /// The offloaded call is cheaper than the native one, always if it can't run on-chain.
+    pub worth_offloading: bool,
+}
+
+///
+/// Estimates the call in a batch of `batch_calls` calls, including it.
+/// Fails if the call can't be proven, as the proof requests do.
+///
+pub fn estimate(prover: &Prover, bytecode: &[u8], calldata: &[u8], batch_calls: u64) -> Result<CostEstimate, String> {
+    // Both executions are bounded by the steps, the gas meter stops early above the block limit.
+    let native_gas = execution_gas(bytecode, Context::new(calldata), MAX_STEPS)?;
+    run_evm_bounded(bytecode, calldata, MAX_STEPS)?;
+
+    let mut batch = ProvingBatch::default();
+    batch.push(bytecode, calldata.to_vec());
+    let cycles = prover.cycles(&batch)?;
+
+    let batch_calls = batch_calls.max(1);
+    let verification_gas = VERIFICATION_GAS.div_ceil(batch_calls);
+    let free_gas = verification_gas + CALL_OVERHEAD_GAS;
+    Ok(CostEstimate {
+        native_gas,
+        cycles,
+        batch_calls,
+        verification_gas,
+        overhead_gas: CALL_OVERHEAD_GAS,
+        free_gas,
+        worth_offloading: match native_gas {
+            Some(native_gas) => free_gas < native_gas,
+            None => true,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::prover::backend::MockBackend;
+    use crate::prover::prover::ProofSystem;
+
+    #[test]
+    fn above_block_gas_limit() {
+        let prover = Prover::new(ProofSystem::Groth16, Box::new(MockBackend::new()));
+        // PUSH2 20000 JUMPDEST PUSH32 MAX DUP1 EXP POP PUSH1 1 SWAP1 SUB DUP1 PUSH1 3 JUMPI STOP,
+        // 20000 exponentiations with a 32-byte exponent for 1644 gas each.
+        let countdown = [
+            &[0x61, 0x4e, 0x20, 0x5b, 0x7f][..],
+            &[0xff; 32],
+            &[0x80, 0x0a, 0x50, 0x60, 0x01, 0x90, 0x03, 0x80, 0x60, 0x03, 0x57, 0x00],
+        ].concat();
+        let cost = estimate(&prover, &countdown, &[], 1).unwrap();
+        assert_eq!(cost.native_gas, None);
+        assert!(cost.worth_offloading);
+        // PUSH0 PUSH5 2^36 MSTORE, past the memory limit, it can't be proven either.
+        assert!(estimate(&prover, &[0x5f, 0x64, 0x10, 0x00, 0x00, 0x00, 0x00, 0x52], &[], 1).is_err());
+
+        // JUMPDEST PUSH0 JUMP, never ends.
+        assert!(estimate(&prover, &[0x5b, 0x5f, 0x56], &[], 1).is_err());
+    }
+}
@@ -0,0 +1,70 @@
+//! Static analysis of the bytecode before it's accepted for proving.
+
+use crate::evm::opcode::Opcode;
+
+/// Forbidden or unsupported opcode found in the code, see `Opcode::is_forbidden` and
+/// `Opcode::is_supported`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForbiddenOpcode {
+    pub pc: usize,
+    pub opcode: u8,
+}
+
+///
+/// Returns all the forbidden opcodes in the code, including unreachable ones, and the bytes the
+/// interpreter can't execute.
+/// Push data and the trailing solc metadata are skipped, as they are never executed.
+///
+pub fn forbidden_opcodes(code: &[u8]) -> Vec<ForbiddenOpcode> {
+    let code = &code[..code.len() - metadata_len(code)];
+    let mut forbidden = vec![];
+    let mut pc = 0;
+    while pc < code.len() {
+        let opcode = code[pc];
+        if Opcode::is_forbidden(opcode) || !Opcode::is_supported(opcode) {
+            forbidden.push(ForbiddenOpcode { pc, opcode });
+        }
+        pc += match opcode {
+            Opcode::PUSH1..=Opcode::PUSH32 => (opcode - Opcode::PUSH1) as usize + 2,
+            _ => 1,
+        };
+    }
+    forbidden
+}
+
+/// Length of the CBOR-encoded metadata appended by solc, followed by its 2-byte length.
+fn metadata_len(code: &[u8]) -> usize {
+    let Some(len_bytes) = code.len().checked_sub(2).map(|start| &code[start..]) else {
+        return 0;
+    };
+    let len = u16::from_be_bytes([len_bytes[0], len_bytes[1]]) as usize;
+    match code.len().checked_sub(len + 2).map(|start| code[start]) {
+        // CBOR map header
+        Some(0xa1..=0xb7) if len > 0 => len + 2,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_evm;
+
+    #[test]
+    fn unsupported_opcodes() {
+        // PUSH1 0x5e MCOPY CALLER TLOAD INVALID
+        let code = [0x60, 0x5e, 0x5e, 0x33, 0x5c, 0xfe];
+        assert_eq!(forbidden_opcodes(&code), vec![
+            ForbiddenOpcode { pc: 2, opcode: 0x5e },
+            ForbiddenOpcode { pc: 3, opcode: 0x33 },
+            ForbiddenOpcode { pc: 4, opcode: 0x5c },
+        ]);
+    }
+
+    #[test]
+    fn accepted_opcodes_execute() {
+        for opcode in 0..=u8::MAX {
+            assert_eq!(forbidden_opcodes(&[opcode]).is_empty(), run_evm(&[opcode], &[]).is_ok(), "0x{:02x}", opcode);
+        }
+    }
+}
@@ -1 +1,2 @@
-pub const WORD_BYTES: usize = 32;
\ No newline at end of file
+pub const WORD_BYTES: usize = 32;
+pub const STACK_LIMIT: usize = 1024;
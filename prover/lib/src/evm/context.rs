@@ -1,3 +1,4 @@
+use crate::evm::consts::WORD_BYTES;
 use primitive_types::{U256};
 
 pub struct Context<'a> {
@@ -20,17 +21,18 @@ impl<'a> Context<'a> {
         call_data_size.into()
     }
 
-    pub fn load_calldata(&self, byte_offset: usize, target_size: usize) -> U256 {
-        let mut res: Vec<u8> = vec![0; target_size];
-
-        for i in 0..target_size {
-            let data_index = i + byte_offset;
-            if data_index < self.call_data.len() {
-                let val = self.call_data[data_index];
-                res[i] = val;
-            }
-        }
+    /// Copies the calldata at `byte_offset` to `dest`, the bytes past its end are zero.
+    pub fn copy_calldata(&self, byte_offset: U256, dest: &mut [u8]) {
+        let start = byte_offset.min(self.call_data.len().into()).as_usize();
+        let data = &self.call_data[start..];
+        let len = data.len().min(dest.len());
+        dest[..len].copy_from_slice(&data[..len]);
+        dest[len..].fill(0);
+    }
 
-        U256::from_big_endian(&res)
+    pub fn load_calldata(&self, byte_offset: U256) -> U256 {
+        let mut word = [0; WORD_BYTES];
+        self.copy_calldata(byte_offset, &mut word);
+        U256::from_big_endian(&word)
     }
-}
\ No newline at end of file
+}
@@ -1,10 +1,30 @@
 use crate::evm::consts::WORD_BYTES;
-use crate::evm::machine::{ControlFlow, EvmError, ExitReason, ExitSuccess, Machine};
+use crate::evm::machine::{ControlFlow, EvmError, ExitSuccess, Machine};
 use crate::evm::opcode::Opcode;
 use crate::evm::{helpers::*};
 use primitive_types::{U256};
 use sha3::{Digest, Keccak256};
 
+/// Unwraps the result of a machine operation, exits with its error.
+macro_rules! check {
+    ($result:expr) => {
+        match $result {
+            Ok(value) => value,
+            Err(err) => return exit_error(err),
+        }
+    };
+}
+
+/// Pops the top of the stack, exits with a stack underflow if it's empty.
+macro_rules! pop {
+    ($machine:expr) => {
+        match $machine.stack.pop() {
+            Some(value) => value,
+            None => return exit_error(EvmError::StackUnderflow),
+        }
+    };
+}
+
 pub fn eval(machine: &mut Machine) -> ControlFlow {
     let opcode = machine.opcode();
     match opcode {
@@ -59,56 +79,22 @@ pub fn eval(machine: &mut Machine) -> ControlFlow {
 
         Opcode::RETURN => eval_return(machine),
 
-        Opcode::DELEGATECALL => forbidden(machine),
-        Opcode::STATICCALL => forbidden(machine),
-        Opcode::SELFDESTRUCT => forbidden(machine),
-        Opcode::ADDRESS => forbidden(machine),
-        Opcode::BALANCE => forbidden(machine),
-        Opcode::ORIGIN => forbidden(machine),
-        Opcode::CALLER => forbidden(machine),
-        Opcode::CODESIZE => forbidden(machine),
-        Opcode::CODECOPY => forbidden(machine),
-        Opcode::BLOCKHASH => forbidden(machine),
-        Opcode::GASPRICE => forbidden(machine),
-        Opcode::EXTCODESIZE => forbidden(machine),
-        Opcode::EXTCODECOPY => forbidden(machine),
-        Opcode::EXTCODEHASH => forbidden(machine),
-        Opcode::RETURNDATASIZE => forbidden(machine),
-        Opcode::RETURNDATACOPY => forbidden(machine),
-        Opcode::COINBASE => forbidden(machine),
-        Opcode::TIMESTAMP => forbidden(machine),
-        Opcode::NUMBER => forbidden(machine),
-        Opcode::DIFFICULTY => forbidden(machine),
-        Opcode::GASLIMIT => forbidden(machine),
-        Opcode::CHAINID => forbidden(machine),
-        Opcode::SELFBALANCE => forbidden(machine),
-        Opcode::BASEFEE => forbidden(machine),
-        Opcode::SLOAD => forbidden(machine),
-        Opcode::SSTORE => forbidden(machine),
-        Opcode::GAS => forbidden(machine),
         Opcode::JUMPDEST => jumpdest(machine),
-        Opcode::LOG0..=Opcode::LOG4 => forbidden(machine),
-        Opcode::CREATE => forbidden(machine),
-        Opcode::CALL => forbidden(machine),
-        // TODO: if fails - use zero
-        Opcode::CALLVALUE => forbidden(machine),
+        opcode if Opcode::is_forbidden(opcode) => forbidden(machine),
 
-        _ => exit_error(EvmError::InvalidInstruction),
+        _ => exit_error(EvmError::UnsupportedOpcode(opcode)),
     }
 }
 
-// TODO: remove unwraps and handle failed stack pops
 // TODO: remove unnecessary mut references for machine
-// TODO: add and handle as_usize or fail
-// TODO: add 1024 stack limit
 
 fn stop(_machine: &mut Machine) -> ControlFlow {
     exit_success(ExitSuccess::Stop)
 }
 
 fn add(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.overflowing_add(b).0;
     machine.stack.push(res);
 
@@ -116,8 +102,8 @@ fn add(machine: &mut Machine) -> ControlFlow {
 }
 
 fn mul(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.overflowing_mul(b).0;
     machine.stack.push(res);
 
@@ -125,8 +111,8 @@ fn mul(machine: &mut Machine) -> ControlFlow {
 }
 
 fn sub(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.overflowing_sub(b).0;
     machine.stack.push(res);
 
@@ -134,8 +120,8 @@ fn sub(machine: &mut Machine) -> ControlFlow {
 }
 
 fn div(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.checked_div(b);
     match res {
         Some(result) => machine.stack.push(result),
@@ -146,8 +132,8 @@ fn div(machine: &mut Machine) -> ControlFlow {
 }
 
 fn sdiv(machine: &mut Machine) -> ControlFlow {
-    let mut a = machine.stack.pop().unwrap();
-    let mut b = machine.stack.pop().unwrap();
+    let mut a = pop!(machine);
+    let mut b = pop!(machine);
 
     // If the first bit is 1, then the value is negative, according to the rules of two's compliment
     let a_is_negative = is_negative(a);
@@ -185,8 +171,8 @@ fn sdiv(machine: &mut Machine) -> ControlFlow {
 }
 
 fn modulus(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.checked_rem(b);
     match res {
         Some(result) => machine.stack.push(result),
@@ -197,8 +183,8 @@ fn modulus(machine: &mut Machine) -> ControlFlow {
 }
 
 fn smodulus(machine: &mut Machine) -> ControlFlow {
-    let mut a = machine.stack.pop().unwrap();
-    let mut b = machine.stack.pop().unwrap();
+    let mut a = pop!(machine);
+    let mut b = pop!(machine);
 
     let a_is_negative = is_negative(a);
     let b_is_negative = is_negative(b);
@@ -229,9 +215,9 @@ fn smodulus(machine: &mut Machine) -> ControlFlow {
 }
 
 fn add_modulus(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
-    let c = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
+    let c = pop!(machine);
     let res = a.overflowing_add(b).0.checked_rem(c);
     match res {
         Some(result) => machine.stack.push(result),
@@ -242,9 +228,9 @@ fn add_modulus(machine: &mut Machine) -> ControlFlow {
 }
 
 fn mul_modulus(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
-    let c = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
+    let c = pop!(machine);
     let res_mul = a.full_mul(b);
     let res_modulo = res_mul.checked_rem(c.into());
     match res_modulo {
@@ -260,8 +246,8 @@ fn mul_modulus(machine: &mut Machine) -> ControlFlow {
 }
 
 fn exp(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = a.overflowing_pow(b).0;
     machine.stack.push(res);
 
@@ -286,8 +272,8 @@ fn exp(machine: &mut Machine) -> ControlFlow {
 // = 1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111110
 // = 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFE
 fn sign_extend(machine: &mut Machine) -> ControlFlow {
-    let num_bytes = machine.stack.pop().unwrap();
-    let int_to_extend = machine.stack.pop().unwrap();
+    let num_bytes = pop!(machine);
+    let int_to_extend = pop!(machine);
 
     if num_bytes >= U256::from(32) {
         // int is already fully extended, EVM is max 256 bits, 32 bytes = 256 bits
@@ -318,8 +304,8 @@ fn sign_extend(machine: &mut Machine) -> ControlFlow {
 }
 
 fn lt(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = (a < b) as u32;
     machine.stack.push(U256::from(res));
 
@@ -327,8 +313,8 @@ fn lt(machine: &mut Machine) -> ControlFlow {
 }
 
 fn gt(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
     let res = (a > b) as u32;
     machine.stack.push(U256::from(res));
 
@@ -336,8 +322,8 @@ fn gt(machine: &mut Machine) -> ControlFlow {
 }
 
 fn slt(machine: &mut Machine) -> ControlFlow {
-    let mut a = machine.stack.pop().unwrap();
-    let mut b = machine.stack.pop().unwrap();
+    let mut a = pop!(machine);
+    let mut b = pop!(machine);
 
     if a == b {
         machine.stack.push(U256::zero());
@@ -374,8 +360,8 @@ fn slt(machine: &mut Machine) -> ControlFlow {
 }
 
 fn sgt(machine: &mut Machine) -> ControlFlow {
-    let mut a = machine.stack.pop().unwrap();
-    let mut b = machine.stack.pop().unwrap();
+    let mut a = pop!(machine);
+    let mut b = pop!(machine);
 
     if a == b {
         machine.stack.push(U256::zero());
@@ -412,8 +398,8 @@ fn sgt(machine: &mut Machine) -> ControlFlow {
 }
 
 fn eq(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
 
     if a == b {
         machine.stack.push(U256::one());
@@ -425,7 +411,7 @@ fn eq(machine: &mut Machine) -> ControlFlow {
 }
 
 fn iszero(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
+    let a = pop!(machine);
 
     if a == U256::zero() {
         machine.stack.push(U256::one());
@@ -437,7 +423,7 @@ fn iszero(machine: &mut Machine) -> ControlFlow {
 }
 
 fn not(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
+    let a = pop!(machine);
 
     machine.stack.push(!a);
 
@@ -445,8 +431,8 @@ fn not(machine: &mut Machine) -> ControlFlow {
 }
 
 fn byte(machine: &mut Machine) -> ControlFlow {
-    let byte_offset = machine.stack.pop().unwrap();
-    let value = machine.stack.pop().unwrap();
+    let byte_offset = pop!(machine);
+    let value = pop!(machine);
 
     if byte_offset >= 32.into() {
         machine.stack.push(U256::zero());
@@ -463,8 +449,8 @@ fn byte(machine: &mut Machine) -> ControlFlow {
 }
 
 fn and(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
 
     machine.stack.push(a & b);
 
@@ -472,8 +458,8 @@ fn and(machine: &mut Machine) -> ControlFlow {
 }
 
 fn or(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
 
     machine.stack.push(a | b);
 
@@ -481,8 +467,8 @@ fn or(machine: &mut Machine) -> ControlFlow {
 }
 
 fn xor(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let b = machine.stack.pop().unwrap();
+    let a = pop!(machine);
+    let b = pop!(machine);
 
     machine.stack.push(a ^ b);
 
@@ -490,8 +476,8 @@ fn xor(machine: &mut Machine) -> ControlFlow {
 }
 
 fn shl(machine: &mut Machine) -> ControlFlow {
-    let shift = machine.stack.pop().unwrap();
-    let value = machine.stack.pop().unwrap();
+    let shift = pop!(machine);
+    let value = pop!(machine);
 
     let shifted = value << shift;
     machine.stack.push(shifted);
@@ -500,8 +486,8 @@ fn shl(machine: &mut Machine) -> ControlFlow {
 }
 
 fn shr(machine: &mut Machine) -> ControlFlow {
-    let shift = machine.stack.pop().unwrap();
-    let value = machine.stack.pop().unwrap();
+    let shift = pop!(machine);
+    let value = pop!(machine);
 
     let shifted = value >> shift;
     machine.stack.push(shifted);
@@ -511,9 +497,9 @@ fn shr(machine: &mut Machine) -> ControlFlow {
 
 fn sar(machine: &mut Machine) -> ControlFlow {
     // shift value is unsigned
-    let shift = machine.stack.pop().unwrap();
+    let shift = pop!(machine);
     // value is signed
-    let mut value = machine.stack.pop().unwrap();
+    let mut value = pop!(machine);
 
     let value_is_negative = is_negative(value);
 
@@ -533,10 +519,10 @@ fn sar(machine: &mut Machine) -> ControlFlow {
 }
 
 fn keccak256(machine: &mut Machine) -> ControlFlow {
-    let offset = machine.stack.pop().unwrap();
-    let size = machine.stack.pop().unwrap();
+    let offset = pop!(machine);
+    let size = pop!(machine);
 
-    let data_to_hash = machine.memory.get(offset.as_usize(), size.as_usize());
+    let data_to_hash = check!(machine.memory.get(offset, size));
     let hashed_data = Keccak256::digest(data_to_hash);
 
     machine.stack.push(U256::from_big_endian(&hashed_data));
@@ -545,13 +531,9 @@ fn keccak256(machine: &mut Machine) -> ControlFlow {
 }
 
 fn calldataload(machine: &mut Machine) -> ControlFlow {
-    let byte_offset = machine.stack.pop().unwrap();
+    let byte_offset = pop!(machine);
 
-    machine.stack.push(
-        machine
-            .context
-            .load_calldata(byte_offset.as_usize(), WORD_BYTES),
-    );
+    machine.stack.push(machine.context.load_calldata(byte_offset));
 
     ControlFlow::Continue(1)
 }
@@ -562,33 +544,27 @@ fn calldatasize(machine: &mut Machine) -> ControlFlow {
     ControlFlow::Continue(1)
 }
 
-// TODO: move all possible .as_usize()'s to the initial values
 fn calldatacopy(machine: &mut Machine) -> ControlFlow {
-    let dest_offset = machine.stack.pop().unwrap();
-    let offset = machine.stack.pop().unwrap();
-    let size = machine.stack.pop().unwrap();
-
-    let calldata = machine
-        .context
-        .load_calldata(offset.as_usize(), size.as_usize());
+    let dest_offset = pop!(machine);
+    let offset = pop!(machine);
+    let size = pop!(machine);
 
-    machine
-        .memory
-        .set(dest_offset.as_usize(), calldata, size.as_usize());
+    let dest = check!(machine.memory.get_mut(dest_offset, size));
+    machine.context.copy_calldata(offset, dest);
 
     ControlFlow::Continue(1)
 }
 
 fn eval_pop(machine: &mut Machine) -> ControlFlow {
-    machine.stack.pop();
+    pop!(machine);
 
     ControlFlow::Continue(1)
 }
 
 fn mload(machine: &mut Machine) -> ControlFlow {
-    let byte_offset = machine.stack.pop().unwrap();
+    let byte_offset = pop!(machine);
 
-    let res = machine.memory.get(byte_offset.as_usize(), WORD_BYTES);
+    let res = check!(machine.memory.get(byte_offset, WORD_BYTES.into()));
     let res_word = U256::from_big_endian(res);
 
     machine.stack.push(res_word);
@@ -596,40 +572,31 @@ fn mload(machine: &mut Machine) -> ControlFlow {
 }
 
 fn mstore(machine: &mut Machine) -> ControlFlow {
-    let byte_offset = machine.stack.pop().unwrap();
-    let value = machine.stack.pop().unwrap();
+    let byte_offset = pop!(machine);
+    let value = pop!(machine);
 
-    machine
-        .memory
-        .set(byte_offset.as_usize(), value, WORD_BYTES);
+    check!(machine.memory.set(byte_offset, value, WORD_BYTES));
 
     ControlFlow::Continue(1)
 }
 
 fn mstore8(machine: &mut Machine) -> ControlFlow {
-    let byte_offset = machine.stack.pop().unwrap();
-    let value = machine.stack.pop().unwrap();
+    let byte_offset = pop!(machine);
+    let value = pop!(machine);
 
-    machine.memory.set(byte_offset.as_usize(), value, 1);
+    check!(machine.memory.set(byte_offset, value, 1));
 
     ControlFlow::Continue(1)
 }
 
 fn forbidden(machine: &mut Machine) -> ControlFlow {
-    println!("{}", machine.opcode());
-    let forbidden_code: U256 = U256::from(123456);
-
-    ControlFlow::Exit(ExitReason::Error(
-        EvmError::Revert(forbidden_code)
-    ))
+    exit_error(EvmError::OpcodeNotStatic(machine.opcode()))
 }
 
 fn jump(machine: &mut Machine) -> ControlFlow {
-    let a = machine.stack.pop().unwrap();
-    let is_valid = true;
-    // let is_valid = machine.jump_map.is_valid(a);
+    let a = pop!(machine);
 
-    if is_valid {
+    if machine.jump_map.is_valid(a) {
         ControlFlow::Jump(a.as_usize())
     } else {
         exit_error(EvmError::InvalidJump)
@@ -637,17 +604,14 @@ fn jump(machine: &mut Machine) -> ControlFlow {
 }
 
 fn jumpi(machine: &mut Machine) -> ControlFlow {
-    let jump_to = machine.stack.pop().unwrap();
-    let should_jump = machine.stack.pop().unwrap();
+    let jump_to = pop!(machine);
+    let should_jump = pop!(machine);
 
     if should_jump.is_zero() {
         return ControlFlow::Continue(1);
     }
 
-    let is_valid = true;
-    // let is_valid = machine.jump_map.is_valid(jump_to);
-
-    if is_valid {
+    if machine.jump_map.is_valid(jump_to) {
         ControlFlow::Jump(jump_to.as_usize())
     } else {
         exit_error(EvmError::InvalidJump)
@@ -685,10 +649,8 @@ fn push_zero(machine: &mut Machine) -> ControlFlow {
 
 fn eval_push(machine: &mut Machine) -> ControlFlow {
     let n = usize::from(machine.opcode() - (Opcode::PUSH1 - 1));
-    let start = machine.pc + 1;
-    let end = start + n;
-    let bytes = &machine.code[start..end];
-    let val_to_push = U256::from_big_endian(bytes);
+    // Push data past the end of the code is zero, the same way as on-chain.
+    let val_to_push = arr_slice_extend(machine.code, machine.pc + 1, n);
     machine.stack.push(val_to_push);
 
     ControlFlow::Continue(n + 1)
@@ -743,21 +705,21 @@ fn swap(machine: &mut Machine) -> ControlFlow {
 // }
 
 fn eval_return(machine: &mut Machine) -> ControlFlow {
-    let offset = machine.stack.pop().unwrap().as_usize();
-    let size = machine.stack.pop().unwrap().as_usize();
+    let offset = pop!(machine);
+    let size = pop!(machine);
 
-    let res = machine.memory.get(offset, size);
+    let res = check!(machine.memory.get(offset, size));
 
     exit_success(ExitSuccess::Return(res.to_vec()))
 }
 
 fn revert(machine: &mut Machine) -> ControlFlow {
-    let offset = machine.stack.pop().unwrap().as_usize();
-    let size = machine.stack.pop().unwrap().as_usize();
+    let offset = pop!(machine);
+    let size = pop!(machine);
 
-    let res = machine.memory.get(offset, size);
+    let res = check!(machine.memory.get(offset, size));
 
-    exit_error(EvmError::Revert(U256::from_big_endian(res)))
+    exit_error(EvmError::Revert(res.to_vec()))
 }
 
 fn invalid(_machine: &mut Machine) -> ControlFlow {
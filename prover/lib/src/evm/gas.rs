@@ -0,0 +1,131 @@
+//! Gas meter of the interpreter, the cost of the execution as a native on-chain call.
+//!
+//! It runs the machine in its own loop, so the execution proven by the program is unchanged.
+
+use primitive_types::U256;
+use crate::evm::consts::WORD_BYTES;
+use crate::evm::context::Context;
+use crate::evm::machine::{EvmError, EvmStatus, Machine};
+use crate::evm::opcode::Opcode;
+
+/// Block gas limit of mainnet, an execution that needs more can't run on-chain.
+pub const MAX_GAS: u64 = 30_000_000;
+
+///
+/// Gas used by the execution with the Shanghai schedule, without the intrinsic cost of the
+/// transaction. `None` if the execution needs more than [`MAX_GAS`], it can't run on-chain.
+/// Fails if the execution doesn't end within `max_steps` steps.
+///
+/// The memory expansion is charged before the step, an execution stops above the limit without
+/// allocating the memory.
+///
+pub fn execution_gas(code: &[u8], context: Context, max_steps: u64) -> Result<Option<u64>, String> {
+    let mut machine = Machine::new(code, context);
+    let mut gas = 0;
+    let mut steps = 0;
+    while machine.pc < machine.code.len() {
+        if steps == max_steps {
+            return Err(format!("Execution takes more than {} steps", max_steps));
+        }
+        steps += 1;
+        let opcode = machine.opcode();
+        gas += static_gas(opcode) + word_gas(&machine, opcode);
+        // Memory past the limit of the machine costs more than the block gas limit.
+        let Ok(words) = expanded_words(&machine, opcode) else {
+            return Ok(None);
+        };
+        gas += memory_gas(words) - memory_gas(memory_words(&machine));
+        if gas > MAX_GAS {
+            return Ok(None);
+        }
+
+        if let EvmStatus::Exited(_) = machine.step() {
+            break;
+        }
+    }
+    Ok(Some(gas))
+}
+
+fn static_gas(opcode: u8) -> u64 {
+    match opcode {
+        Opcode::STOP | Opcode::RETURN | Opcode::REVERT | Opcode::INVALID => 0,
+        Opcode::JUMPDEST => 1,
+        Opcode::CALLDATASIZE | Opcode::POP | Opcode::PC | Opcode::MSIZE | Opcode::PUSH0 => 2,
+        Opcode::ADD | Opcode::SUB | Opcode::NOT | Opcode::LT | Opcode::GT | Opcode::SLT | Opcode::SGT
+        | Opcode::EQ | Opcode::ISZERO | Opcode::AND | Opcode::OR | Opcode::XOR | Opcode::BYTE
+        | Opcode::SHL | Opcode::SHR | Opcode::SAR | Opcode::CALLDATALOAD | Opcode::CALLDATACOPY
+        | Opcode::MLOAD | Opcode::MSTORE | Opcode::MSTORE8 => 3,
+        Opcode::PUSH1..=Opcode::PUSH32 | Opcode::DUP1..=Opcode::DUP16 | Opcode::SWAP1..=Opcode::SWAP16 => 3,
+        Opcode::MUL | Opcode::DIV | Opcode::SDIV | Opcode::MOD | Opcode::SMOD | Opcode::SIGNEXTEND => 5,
+        Opcode::ADDMOD | Opcode::MULMOD | Opcode::JUMP => 8,
+        Opcode::JUMPI | Opcode::EXP => 10,
+        Opcode::KECCAK256 => 30,
+        // Other opcodes fail the execution.
+        _ => 0,
+    }
+}
+
+/// Cost depending on the size of the operands.
+fn word_gas(machine: &Machine, opcode: u8) -> u64 {
+    let operand = |i| machine.stack.peek(i).unwrap_or_default();
+    let words = |size: U256| (size.low_u64() as usize).div_ceil(WORD_BYTES) as u64;
+    match opcode {
+        Opcode::EXP => 50 * operand(1).bits().div_ceil(8) as u64,
+        Opcode::KECCAK256 => 6 * words(operand(1)),
+        Opcode::CALLDATACOPY => 3 * words(operand(2)),
+        _ => 0,
+    }
+}
+
+/// Memory words after the step, the operands are read before the machine accesses the memory.
+fn expanded_words(machine: &Machine, opcode: u8) -> Result<u64, EvmError> {
+    let operand = |i| machine.stack.peek(i).unwrap_or_default();
+    let (offset, size) = match opcode {
+        Opcode::KECCAK256 | Opcode::RETURN | Opcode::REVERT => (operand(0), operand(1)),
+        Opcode::CALLDATACOPY => (operand(0), operand(2)),
+        Opcode::MLOAD | Opcode::MSTORE => (operand(0), WORD_BYTES.into()),
+        Opcode::MSTORE8 => (operand(0), U256::one()),
+        _ => return Ok(memory_words(machine)),
+    };
+    Ok((machine.memory.expanded_size(offset, size)? / WORD_BYTES) as u64)
+}
+
+fn memory_words(machine: &Machine) -> u64 {
+    (machine.memory.size() / WORD_BYTES) as u64
+}
+
+fn memory_gas(words: u64) -> u64 {
+    3 * words + words * words / 512
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn gas_and_steps() {
+        // CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN
+        let echo = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+        assert_eq!(execution_gas(&echo, Context::new(&[1, 2]), 7), Ok(Some(19)));
+        assert!(execution_gas(&echo, Context::new(&[1, 2]), 6).is_err());
+
+        // JUMPDEST PUSH0 JUMP, 11 gas every 3 steps.
+        let endless = [0x5b, 0x5f, 0x56];
+        assert_eq!(execution_gas(&endless, Context::new(&[]), 10_000_000), Ok(None));
+        assert!(execution_gas(&endless, Context::new(&[]), 1_000_000).is_err());
+    }
+
+    #[test]
+    fn memory_expansion() {
+        // PUSH0 PUSH2 0x1000 MSTORE, 129 words of memory.
+        let store = [0x5f, 0x61, 0x10, 0x00, 0x52];
+        assert_eq!(execution_gas(&store, Context::new(&[]), 10), Ok(Some(2 + 3 + 3 + memory_gas(129))));
+
+        // PUSH0 PUSH3 0x400000 MSTORE, 131073 words cost more than the block gas limit.
+        let above_limit = [0x5f, 0x62, 0x40, 0x00, 0x00, 0x52];
+        assert_eq!(execution_gas(&above_limit, Context::new(&[]), 10), Ok(None));
+        // PUSH0 PUSH5 2^36 MSTORE, past the memory of the machine.
+        let far_store = [0x5f, 0x64, 0x10, 0x00, 0x00, 0x00, 0x00, 0x52];
+        assert_eq!(execution_gas(&far_store, Context::new(&[]), 10), Ok(None));
+    }
+}
@@ -10,29 +10,17 @@ impl JumpMap {
     }
 
     fn generate_map(code: &[u8]) -> Vec<bool> {
-        let mut map: Vec<bool> = vec![];
-        let mut steps_to_block = 0;
-        for i in 0..code.len() {
-            if steps_to_block > 0 {
-                steps_to_block -= 1;
-            }
-
-            let opcode = code[i];
-
-            match opcode {
-                Opcode::PUSH1..=Opcode::PUSH32 => {
-                    steps_to_block = usize::from(opcode) - usize::from(Opcode::PUSH1) + 2;
-                }
+        let mut map = vec![false; code.len()];
+        let mut i = 0;
+        while i < code.len() {
+            match code[i] {
+                Opcode::JUMPDEST => map[i] = true,
+                // Push data is never a jump destination.
+                opcode @ Opcode::PUSH1..=Opcode::PUSH32 => i += usize::from(opcode - Opcode::PUSH1) + 1,
                 _ => (),
             }
-
-            if steps_to_block == 0 && opcode == Opcode::JUMPDEST {
-                map.push(true);
-            } else {
-                map.push(false);
-            }
+            i += 1;
         }
-
         map
     }
 
@@ -1,8 +1,9 @@
-use crate::evm::{block::Block, context::Context};
+use crate::evm::{block::Block, consts::STACK_LIMIT, context::Context};
 use crate::evm::eval::eval;
 use crate::evm::jump_map::JumpMap;
 use crate::evm::memory::Memory;
 use crate::evm::stack::Stack;
+use crate::MAX_MEMORY;
 use primitive_types::{U256, H160};
 
 pub enum ControlFlow {
@@ -21,16 +22,21 @@ pub enum ExitSuccess {
     Return(Vec<u8>),
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone)]
 pub enum EvmError {
     StackUnderflow,
+    StackOverflow,
     InvalidInstruction,
     InvalidJump,
-    Revert(U256),
+    Revert(Vec<u8>),
     OpcodeNotStatic(u8),
+    /// Memory access past the limit, in bytes.
+    MemoryLimit(usize),
+    /// Opcode the interpreter doesn't implement, the execution can't be proven.
+    UnsupportedOpcode(u8),
 }
 
-enum EvmStatus {
+pub(crate) enum EvmStatus {
     Running,
     Exited(ExitReason),
 }
@@ -91,7 +97,7 @@ impl<'a> Machine<'a> {
     ) -> Self {
         Self {
             stack: Stack::new(),
-            memory: Memory::new(),
+            memory: Memory::new(MAX_MEMORY),
             jump_map: JumpMap::new(code),
             return_data_buffer: Vec::new(),
             logs: Vec::new(),
@@ -109,8 +115,12 @@ impl<'a> Machine<'a> {
         self.code[self.pc]
     }
 
-    fn step(&mut self) -> EvmStatus {
-        match eval(self) {
+    pub(crate) fn step(&mut self) -> EvmStatus {
+        let flow = eval(self);
+        if self.stack.len() > STACK_LIMIT {
+            return EvmStatus::Exited(ExitReason::Error(EvmError::StackOverflow));
+        }
+        match flow {
             ControlFlow::Continue(steps) => {
                 self.pc += steps;
                 EvmStatus::Running
@@ -124,41 +134,52 @@ impl<'a> Machine<'a> {
     }
 
     pub fn execute(&mut self) -> EvmResult {
+        // Only a bounded execution stops early.
+        self.execute_bounded(None).unwrap()
+    }
+
+    /// `None` if the execution doesn't end within `max_steps` steps.
+    pub fn execute_bounded(&mut self, max_steps: Option<u64>) -> Option<EvmResult> {
+        let mut steps = 0;
         while self.pc < self.code.len() {
+            if max_steps == Some(steps) {
+                return None;
+            }
+            steps += 1;
             match self.step() {
                 EvmStatus::Running => continue,
                 EvmStatus::Exited(reason) => match reason {
                     ExitReason::Success(success) => match success {
                         ExitSuccess::Stop => break,
                         ExitSuccess::Return(val) => {
-                            return EvmResult {
+                            return Some(EvmResult {
                                 stack: self.stack(),
                                 success: true,
                                 error: None,
                                 logs: self.logs.clone(),
                                 return_val: Some(val),
-                            }
+                            })
                         }
                     },
                     ExitReason::Error(error) => {
-                        return EvmResult {
+                        return Some(EvmResult {
                             stack: self.stack(),
                             success: false,
                             error: Some(error),
                             logs: self.logs.clone(),
                             return_val: None,
-                        }
+                        })
                     }
                 },
             }
         }
 
-        EvmResult {
+        Some(EvmResult {
             stack: self.stack(),
             success: true,
             error: None,
             logs: self.logs.clone(),
             return_val: None,
-        }
+        })
     }
 }
\ No newline at end of file
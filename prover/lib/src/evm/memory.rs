@@ -1,16 +1,19 @@
-use crate::evm::{consts::WORD_BYTES, helpers::ceil_divide};
+use crate::evm::{consts::WORD_BYTES, helpers::ceil_divide, machine::EvmError};
 use primitive_types::U256;
 
 pub struct Memory {
     data: Vec<u8>,
     len_words: usize,
+    limit: usize,
 }
 
 impl Memory {
-    pub fn new() -> Self {
+    /// Memory of at most `limit` bytes, an access past it fails the execution.
+    pub fn new(limit: usize) -> Self {
         Self {
             data: Vec::new(),
             len_words: 0,
+            limit,
         }
     }
 
@@ -18,27 +21,54 @@ impl Memory {
         self.len_words * WORD_BYTES
     }
 
-    fn resize(&mut self, length: usize) {
-        if length >= self.len_words * WORD_BYTES {
-            self.data.resize(length, 0);
-            self.len_words = ceil_divide(length, WORD_BYTES);
+    ///
+    /// Size of the memory after an access of `size` bytes at `byte_offset`, an empty access
+    /// doesn't expand it.
+    ///
+    pub fn expanded_size(&self, byte_offset: U256, size: U256) -> Result<usize, EvmError> {
+        if size.is_zero() {
+            return Ok(self.size());
         }
+        match byte_offset.checked_add(size) {
+            Some(end) if end <= self.limit.into() => {
+                Ok(self.size().max(ceil_divide(end.as_usize(), WORD_BYTES) * WORD_BYTES))
+            }
+            _ => Err(EvmError::MemoryLimit(self.limit)),
+        }
+    }
+
+    /// Range of the access, the memory is expanded to contain it.
+    fn access(&mut self, byte_offset: U256, size: U256) -> Result<std::ops::Range<usize>, EvmError> {
+        if size.is_zero() {
+            return Ok(0..0);
+        }
+        let expanded_size = self.expanded_size(byte_offset, size)?;
+        if expanded_size > self.data.len() {
+            self.data.resize(expanded_size, 0);
+            self.len_words = expanded_size / WORD_BYTES;
+        }
+        // Within the limit, checked above.
+        let start = byte_offset.as_usize();
+        Ok(start..start + size.as_usize())
     }
 
     // memory′[offset . . . (offset + 31)] ≡ value
     // num_words_in_mem′≡max(num_words_in_mem, ceil( (offset+32)÷32 ) )
-    pub fn set(&mut self, byte_offset: usize, value: U256, target_size: usize) {
-        self.resize(byte_offset + target_size);
-
-        for i in 0..target_size {
-            let byte = value.byte(target_size - 1 - i);
-            self.data[byte_offset + i] = byte;
+    pub fn set(&mut self, byte_offset: U256, value: U256, target_size: usize) -> Result<(), EvmError> {
+        let range = self.access(byte_offset, target_size.into())?;
+        for (i, byte) in self.data[range].iter_mut().enumerate() {
+            *byte = value.byte(target_size - 1 - i);
         }
+        Ok(())
+    }
+
+    pub fn get(&mut self, byte_offset: U256, target_size: U256) -> Result<&[u8], EvmError> {
+        let range = self.access(byte_offset, target_size)?;
+        Ok(&self.data[range])
     }
 
-    pub fn get(&mut self, byte_offset: usize, target_size: usize) -> &[u8] {
-        let end_index = byte_offset + target_size;
-        self.resize(end_index);
-        &self.data[byte_offset..end_index]
+    pub fn get_mut(&mut self, byte_offset: U256, target_size: U256) -> Result<&mut [u8], EvmError> {
+        let range = self.access(byte_offset, target_size)?;
+        Ok(&mut self.data[range])
     }
-}
\ No newline at end of file
+}
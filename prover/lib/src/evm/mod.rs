@@ -1,7 +1,9 @@
+pub mod analysis;
 pub mod block;
 mod consts;
 pub mod context;
 mod eval;
+pub mod gas;
 pub mod helpers;
 mod jump_map;
 mod machine;
@@ -10,12 +12,39 @@ mod opcode;
 mod stack;
 
 use context::Context;
-use machine::EvmResult;
-use machine::Machine;
+use machine::{EvmStatus, Machine};
+pub use machine::{EvmError, EvmResult};
 
 pub fn evm(
     code: impl AsRef<[u8]>,
     context: Context,
 ) -> EvmResult {
     Machine::new(code.as_ref(), context).execute()
-}
\ No newline at end of file
+}
+/// `None` if the execution doesn't end within `max_steps` steps.
+pub fn evm_bounded(
+    code: impl AsRef<[u8]>,
+    context: Context,
+    max_steps: u64,
+) -> Option<EvmResult> {
+    Machine::new(code.as_ref(), context).execute_bounded(Some(max_steps))
+}
+/// Steps of the execution, `None` if it doesn't end within `max_steps` steps.
+pub fn evm_steps(
+    code: impl AsRef<[u8]>,
+    context: Context,
+    max_steps: u64,
+) -> Option<u64> {
+    let mut machine = Machine::new(code.as_ref(), context);
+    let mut steps = 0;
+    while machine.pc < machine.code.len() {
+        if steps == max_steps {
+            return None;
+        }
+        steps += 1;
+        if let EvmStatus::Exited(_) = machine.step() {
+            break;
+        }
+    }
+    Some(steps)
+}
@@ -97,4 +97,56 @@ impl Opcode {
             _ => true,
         }
     }
-}
\ No newline at end of file
+
+    /// Opcodes implemented by the interpreter, matches `eval` without the forbidden ones.
+    pub fn is_supported(value: u8) -> bool {
+        matches!(
+            value,
+            Opcode::STOP..=Opcode::SIGNEXTEND
+                | Opcode::LT..=Opcode::SAR
+                | Opcode::KECCAK256
+                | Opcode::CALLDATALOAD..=Opcode::CALLDATACOPY
+                | Opcode::POP..=Opcode::MSTORE8
+                | Opcode::JUMP..=Opcode::MSIZE
+                | Opcode::JUMPDEST
+                | Opcode::PUSH0..=Opcode::SWAP16
+                | Opcode::RETURN
+                | Opcode::REVERT
+                | Opcode::INVALID
+        )
+    }
+
+    /// Opcodes depending on the state or the environment, they fail the execution.
+    pub fn is_forbidden(value: u8) -> bool {
+        // TODO: CALLVALUE could be zero instead
+        !Opcode::is_static(value) || matches!(
+            value,
+            Opcode::DELEGATECALL
+                | Opcode::STATICCALL
+                | Opcode::ADDRESS
+                | Opcode::BALANCE
+                | Opcode::ORIGIN
+                | Opcode::CALLER
+                | Opcode::CALLVALUE
+                | Opcode::CODESIZE
+                | Opcode::CODECOPY
+                | Opcode::BLOCKHASH
+                | Opcode::GASPRICE
+                | Opcode::EXTCODESIZE
+                | Opcode::EXTCODECOPY
+                | Opcode::EXTCODEHASH
+                | Opcode::RETURNDATASIZE
+                | Opcode::RETURNDATACOPY
+                | Opcode::COINBASE
+                | Opcode::TIMESTAMP
+                | Opcode::NUMBER
+                | Opcode::DIFFICULTY
+                | Opcode::GASLIMIT
+                | Opcode::CHAINID
+                | Opcode::SELFBALANCE
+                | Opcode::BASEFEE
+                | Opcode::SLOAD
+                | Opcode::GAS
+        )
+    }
+}
@@ -18,6 +18,10 @@ impl Stack {
         self.data.pop()
     }
 
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
     pub fn set(&mut self, value: U256, i: usize) -> Result<(), EvmError> {
         let len = self.data.len();
         if len > i {
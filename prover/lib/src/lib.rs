@@ -1,9 +1,22 @@
+pub mod batch;
+#[cfg(feature = "host")]
+pub mod estimate;
 pub mod evm;
+pub mod merkle;
+#[cfg(feature = "host")]
 pub mod prover;
 
 use alloy_sol_types::sol;
+use serde::{Deserialize, Serialize};
 use crate::evm::context::Context;
-use crate::evm::evm;
+use crate::evm::{evm, evm_bounded, EvmError, EvmResult};
+
+/// Limit of the interpreter steps of a call executed by the server, the program executes the
+/// accepted calls unbounded.
+pub const MAX_STEPS: u64 = 10_000_000;
+
+/// Limit of the memory of an execution in bytes, an execution needing more can't be proven.
+pub const MAX_MEMORY: usize = 16 * 1024 * 1024;
 
 sol! {
     struct PublicValuesStruct {
@@ -11,6 +24,21 @@ sol! {
     }
 }
 
+/// Status of the proven execution, committed as the last byte of the leaf.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(u8)]
+pub enum ExecutionStatus {
+    Success = 0,
+    Revert = 1,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExecutionOutcome {
+    pub status: ExecutionStatus,
+    /// Return data on success, revert data otherwise.
+    pub output: Vec<u8>,
+}
+
 pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
     use tiny_keccak::{Hasher, Keccak};
 
@@ -21,15 +49,126 @@ pub fn keccak256(bytes: &[u8]) -> [u8; 32] {
     output
 }
 
-pub fn run_evm(bytecode: Vec<u8>, calldata: Vec<u8>) -> Result<Vec<u8>, String> {
-    let result = evm(
-        bytecode.as_slice(),
-        Context::new(calldata.as_slice()),
-    );
+///
+/// Leaf of the batch Merkle tree, matches `ComputationOracle.verifyComputation`:
+/// `keccak256(codeHash ++ keccak256(calldata) ++ keccak256(output) ++ status)`.
+///
+pub fn leaf_hash(code_hash: [u8; 32], calldata: &[u8], outcome: &ExecutionOutcome) -> [u8; 32] {
+    keccak256(
+        [
+            &code_hash[..],
+            &keccak256(calldata)[..],
+            &keccak256(outcome.output.as_slice())[..],
+            &[outcome.status as u8][..],
+        ].concat().as_slice()
+    )
+}
+
+///
+/// Executes the bytecode, reverts are a valid (provable) outcome.
+/// Returns an error only if the execution depends on the state or needs more than
+/// [`MAX_MEMORY`] bytes of memory, it can't be proven.
+///
+pub fn run_evm(bytecode: &[u8], calldata: &[u8]) -> Result<ExecutionOutcome, String> {
+    outcome(evm(
+        bytecode,
+        Context::new(calldata),
+    ))
+}
+
+///
+/// Same as [`run_evm`], but also fails if the execution doesn't end within `max_steps` steps.
+/// The memory is bounded the same way, the execution of a call by the server is bounded in time
+/// and space.
+///
+pub fn run_evm_bounded(bytecode: &[u8], calldata: &[u8], max_steps: u64) -> Result<ExecutionOutcome, String> {
+    let result = evm_bounded(bytecode, Context::new(calldata), max_steps)
+        .ok_or(format!("Execution takes more than {} steps", max_steps))?;
+    outcome(result)
+}
+
+fn outcome(result: EvmResult) -> Result<ExecutionOutcome, String> {
+    match result.error {
+        None => Ok(ExecutionOutcome {
+            status: ExecutionStatus::Success,
+            output: result.return_val.unwrap_or_default(),
+        }),
+        Some(EvmError::OpcodeNotStatic(opcode)) => Err(format!("Non-static opcode 0x{:02x}", opcode)),
+        Some(EvmError::UnsupportedOpcode(opcode)) => Err(format!("Unsupported opcode 0x{:02x}", opcode)),
+        Some(EvmError::MemoryLimit(limit)) => Err(format!("Execution needs more than {} bytes of memory", limit)),
+        Some(EvmError::Revert(data)) => Ok(ExecutionOutcome {
+            status: ExecutionStatus::Revert,
+            output: data,
+        }),
+        // Exceptional halts revert without data, the same way as on-chain.
+        Some(EvmError::StackUnderflow | EvmError::StackOverflow | EvmError::InvalidInstruction | EvmError::InvalidJump) => Ok(ExecutionOutcome {
+            status: ExecutionStatus::Revert,
+            output: vec![],
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    if !result.success {
-        return Err(format!("Unsuccessful execution, {:?}", result.error));
+    #[test]
+    fn bounded_execution() {
+        // JUMPDEST PUSH0 JUMP
+        assert!(run_evm_bounded(&[0x5b, 0x5f, 0x56], &[], 1000).is_err());
+        // CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN
+        let echo = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+        assert_eq!(run_evm_bounded(&echo, &[1, 2], 7), run_evm(&echo, &[1, 2]));
+        assert!(run_evm_bounded(&echo, &[1, 2], 6).is_err());
     }
 
-    Ok(result.return_val.ok_or("missing return value")?)
+    #[test]
+    fn bounded_memory() {
+        // PUSH0 PUSH5 2^36 MSTORE
+        let far_store = [0x5f, 0x64, 0x10, 0x00, 0x00, 0x00, 0x00, 0x52];
+        let error = Err(format!("Execution needs more than {} bytes of memory", MAX_MEMORY));
+        assert_eq!(run_evm_bounded(&far_store, &[], MAX_STEPS), error);
+        // PUSH0 PUSH32 MAX MSTORE, the end of the word overflows.
+        let overflow = [&[0x5f, 0x7f][..], &[0xff; 32], &[0x52]].concat();
+        assert_eq!(run_evm_bounded(&overflow, &[], MAX_STEPS), error);
+        // PUSH0 PUSH32 MAX RETURN, an empty access doesn't expand the memory.
+        let empty_return = [&[0x5f, 0x7f][..], &[0xff; 32], &[0xf3]].concat();
+        let empty = ExecutionOutcome { status: ExecutionStatus::Success, output: vec![] };
+        assert_eq!(run_evm_bounded(&empty_return, &[], MAX_STEPS), Ok(empty));
+        // PUSH1 64 PUSH0 PUSH0 CALLDATACOPY PUSH1 64 PUSH0 RETURN, copies past the calldata.
+        let copy = [0x60, 0x40, 0x5f, 0x5f, 0x37, 0x60, 0x40, 0x5f, 0xf3];
+        let calldata = [7; 40];
+        let output = [&calldata[..], &[0; 24]].concat();
+        let copied = ExecutionOutcome { status: ExecutionStatus::Success, output };
+        assert_eq!(run_evm_bounded(&copy, &calldata, MAX_STEPS), Ok(copied));
+    }
+
+    #[test]
+    fn unsupported_opcodes() {
+        // PUSH0 PUSH0 PUSH0 MCOPY
+        assert_eq!(run_evm(&[0x5f, 0x5f, 0x5f, 0x5e], &[]), Err("Unsupported opcode 0x5e".to_string()));
+        // PUSH0 TLOAD
+        assert_eq!(run_evm(&[0x5f, 0x5c], &[]), Err("Unsupported opcode 0x5c".to_string()));
+        // INVALID
+        let invalid = ExecutionOutcome { status: ExecutionStatus::Revert, output: vec![] };
+        assert_eq!(run_evm(&[0xfe], &[]), Ok(invalid));
+    }
+
+    #[test]
+    fn exceptional_halts() {
+        let revert = Ok(ExecutionOutcome { status: ExecutionStatus::Revert, output: vec![] });
+        // ADD
+        assert_eq!(run_evm(&[0x01], &[]), revert);
+        // POP
+        assert_eq!(run_evm(&[0x50], &[]), revert);
+        // PUSH1 4 JUMP PUSH1 JUMPDEST, the destination is push data.
+        assert_eq!(run_evm(&[0x60, 0x04, 0x56, 0x60, 0x5b], &[]), revert);
+        // PUSH32 MAX JUMP
+        assert_eq!(run_evm(&[&[0x7f][..], &[0xff; 32], &[0x56]].concat(), &[]), revert);
+        // PUSH0 x 1025
+        assert_eq!(run_evm(&[0x5f; 1025], &[]), revert);
+        // PUSH1 3 JUMP JUMPDEST PUSH2 1, the push data is truncated.
+        let success = Ok(ExecutionOutcome { status: ExecutionStatus::Success, output: vec![] });
+        assert_eq!(run_evm(&[0x60, 0x03, 0x56, 0x5b, 0x61, 0x01], &[]), success);
+    }
 }
@@ -0,0 +1,121 @@
+//! Merkle tree over the batch leaves, compatible with OpenZeppelin `MerkleProof.processProof`.
+
+use crate::keccak256;
+
+/// Pairs are hashed sorted, so the proof doesn't need to encode the sides.
+pub fn hash_pair(a: [u8; 32], b: [u8; 32]) -> [u8; 32] {
+    let (left, right) = if a <= b { (a, b) } else { (b, a) };
+    keccak256(
+        [
+            left,
+            right
+        ].concat().as_slice()
+    )
+}
+
+pub struct MerkleTree {
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    /// Leaves are sorted, so the tree doesn't depend on the order of the requests.
+    pub fn new(mut leafs: Vec<[u8; 32]>) -> Self {
+        assert!(!leafs.is_empty(), "Merkle tree without leafs");
+        leafs.sort_by(|x, y| y.cmp(x));
+
+        let mut levels = vec![leafs];
+        while levels.last().unwrap().len() > 1 {
+            let current_level = levels.last().unwrap();
+            let mut next_level = Vec::new();
+
+            // Process pairs of nodes
+            for i in (0..current_level.len()).step_by(2) {
+                if i + 1 < current_level.len() {
+                    // Hash the pair
+                    next_level.push(hash_pair(current_level[i], current_level[i + 1]));
+                } else {
+                    // Odd number of nodes, duplicate the last node
+                    next_level.push(hash_pair(current_level[i], current_level[i]));
+                }
+            }
+
+            levels.push(next_level);
+        }
+
+        Self { levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Sibling hashes from the leaf to the root, `None` if the leaf is not in the tree.
+    pub fn proof(&self, leaf: [u8; 32]) -> Option<Vec<[u8; 32]>> {
+        let mut index = self.levels[0].iter().position(|node| *node == leaf)?;
+        let mut proof = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = (index ^ 1).min(level.len() - 1);
+            proof.push(level[sibling]);
+            index /= 2;
+        }
+        Some(proof)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `MerkleProof.processProof` of OpenZeppelin, with `Hashes.commutativeKeccak256`.
+    fn process_proof(leaf: [u8; 32], proof: &[[u8; 32]]) -> [u8; 32] {
+        proof.iter().fold(leaf, |computed, node| {
+            if computed < *node {
+                keccak256(&[computed, *node].concat())
+            } else {
+                keccak256(&[*node, computed].concat())
+            }
+        })
+    }
+
+    fn leafs(count: u8) -> Vec<[u8; 32]> {
+        (0..count).map(|i| keccak256(&[i])).collect()
+    }
+
+    #[test]
+    fn proofs_verify() {
+        // Odd levels included, the last node is paired with itself.
+        for count in 1..=9 {
+            let tree = MerkleTree::new(leafs(count));
+            for leaf in leafs(count) {
+                let proof = tree.proof(leaf).unwrap();
+                assert_eq!(process_proof(leaf, &proof), tree.root(), "leaf count {}", count);
+            }
+        }
+    }
+
+    #[test]
+    fn single_leaf() {
+        let leaf = keccak256(b"leaf");
+        let tree = MerkleTree::new(vec![leaf]);
+        assert_eq!(tree.root(), leaf);
+        assert_eq!(tree.proof(leaf), Some(vec![]));
+        assert_eq!(tree.proof(keccak256(b"other")), None);
+    }
+
+    #[test]
+    fn leaf_order() {
+        let mut reversed = leafs(5);
+        reversed.reverse();
+        assert_eq!(MerkleTree::new(leafs(5)).root(), MerkleTree::new(reversed).root());
+    }
+
+    #[test]
+    fn duplicate_leafs() {
+        // The same call proven twice in a batch.
+        let mut duplicated = leafs(3);
+        duplicated.push(duplicated[1]);
+        let tree = MerkleTree::new(duplicated);
+        let proof = tree.proof(leafs(3)[1]).unwrap();
+        assert_eq!(process_proof(leafs(3)[1], &proof), tree.root());
+    }
+}
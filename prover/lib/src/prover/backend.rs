@@ -0,0 +1,308 @@
+//! Backends generating the proofs of the prover program, selected with [`BackendConfig`].
+
+use std::collections::HashMap;
+use std::time::Duration;
+use alloy_sol_types::SolType;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::network::client::NetworkClient;
+use sp1_sdk::network::proto::network::{ProofMode, ProofStatus};
+use sp1_sdk::{
+    block_on, PlonkBn254Proof, ProverClient, SP1Proof, SP1ProofWithPublicValues, SP1ProvingKey, SP1PublicValues, SP1Stdin,
+    SP1VerifyingKey, SP1_CIRCUIT_VERSION,
+};
+use crate::evm::context::Context;
+use crate::evm::evm_steps;
+use crate::merkle::MerkleTree;
+use crate::batch::ProvingBatch;
+use crate::prover::prover::ProofSystem;
+use crate::{keccak256, PublicValuesStruct};
+
+/// Executes and proves the prover program, the keys are set up once by `Prover`.
+pub trait ProverBackend: Send + Sync {
+    fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey);
+
+    /// Executes the program without proving, returns its total zkVM cycles.
+    /// Fails if the execution takes more than `max_cycles` cycles.
+    fn cycles(&self, elf: &[u8], batch: &ProvingBatch, max_cycles: u64) -> Result<u64, String>;
+
+    /// Blocks until the proof is generated.
+    fn prove(&self, pk: &SP1ProvingKey, batch: &ProvingBatch, system: ProofSystem) -> Result<SP1ProofWithPublicValues, String>;
+}
+
+/// The local backend is the SDK client proving on the CPU.
+impl ProverBackend for ProverClient {
+    fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+        ProverClient::setup(self, elf)
+    }
+
+    fn cycles(&self, elf: &[u8], batch: &ProvingBatch, max_cycles: u64) -> Result<u64, String> {
+        let (_, report) = self.execute(elf, stdin(batch))
+            .max_cycles(max_cycles)
+            .run()
+            .map_err(|err| err.to_string())?;
+        Ok(report.total_instruction_count())
+    }
+
+    fn prove(&self, pk: &SP1ProvingKey, batch: &ProvingBatch, system: ProofSystem) -> Result<SP1ProofWithPublicValues, String> {
+        let proof = match system {
+            ProofSystem::Plonk => ProverClient::prove(self, pk, stdin(batch)).plonk().run(),
+            ProofSystem::Groth16 => ProverClient::prove(self, pk, stdin(batch)).groth16().run(),
+        };
+        proof.map_err(|err| err.to_string())
+    }
+}
+
+fn stdin(batch: &ProvingBatch) -> SP1Stdin {
+    let mut stdin = SP1Stdin::new();
+    stdin.write(batch);
+    stdin
+}
+
+///
+/// Runs the logic of the prover program natively and wraps its public values into a mock proof,
+/// so a batch is "proven" in milliseconds. The oracle accepts it only with the dummy verifier.
+/// The keys still come from the SP1 mock client, the cycles are estimated from the interpreter
+/// steps instead of executing the ELF, which may be older than the batch format.
+///
+/// Rough zkVM cycles of an interpreter step of the program, and of the hashing around every call.
+const MOCK_STEP_CYCLES: u64 = 1_000;
+const MOCK_CALL_CYCLES: u64 = 50_000;
+
+pub struct MockBackend {
+    client: ProverClient,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Self { client: ProverClient::mock() }
+    }
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProverBackend for MockBackend {
+    fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+        self.client.setup(elf)
+    }
+
+    fn cycles(&self, _elf: &[u8], batch: &ProvingBatch, max_cycles: u64) -> Result<u64, String> {
+        let bytecodes = batch.bytecodes
+            .iter()
+            .map(|bytecode| (keccak256(bytecode), bytecode))
+            .collect::<HashMap<_, _>>();
+        let mut cycles = 0;
+        for request in &batch.requests {
+            cycles += MOCK_CALL_CYCLES;
+            // Requests without their bytecode aren't executed by the program either.
+            if let Some(bytecode) = bytecodes.get(&request.code_hash) {
+                let max_steps = max_cycles.saturating_sub(cycles) / MOCK_STEP_CYCLES;
+                let steps = evm_steps(bytecode, Context::new(&request.calldata), max_steps)
+                    .ok_or(format!("Execution takes more than {} cycles", max_cycles))?;
+                cycles += steps * MOCK_STEP_CYCLES;
+            }
+        }
+        if cycles > max_cycles {
+            return Err(format!("Execution takes more than {} cycles", max_cycles));
+        }
+        Ok(cycles)
+    }
+
+    fn prove(&self, _pk: &SP1ProvingKey, batch: &ProvingBatch, _system: ProofSystem) -> Result<SP1ProofWithPublicValues, String> {
+        // Same as the program, see `program/src/main.rs`.
+        let leaves = batch.execute().into_iter().flatten().map(|(leaf, _)| leaf).collect::<Vec<_>>();
+        if leaves.is_empty() {
+            return Err("No provable requests in the batch".to_string());
+        }
+        let public_values = PublicValuesStruct::abi_encode(&PublicValuesStruct {
+            _merkleRoot: MerkleTree::new(leaves).root().into(),
+        });
+
+        // The proof bytes of a mock proof are empty, whatever the proof system is.
+        Ok(SP1ProofWithPublicValues {
+            proof: SP1Proof::Plonk(PlonkBn254Proof {
+                public_inputs: Default::default(),
+                encoded_proof: String::new(),
+                raw_proof: String::new(),
+                plonk_vkey_hash: [0; 32],
+            }),
+            stdin: stdin(batch),
+            public_values: SP1PublicValues::from(&public_values),
+            sp1_version: SP1_CIRCUIT_VERSION.to_string(),
+        })
+    }
+}
+
+/// Interval between the status requests of a network proof, and the failed requests tolerated in a row.
+const NETWORK_POLL_INTERVAL: Duration = Duration::from_secs(2);
+const NETWORK_MAX_ERRORS: usize = 10;
+
+///
+/// Requests the proofs from the prover network with the SDK network client, the keys are set up
+/// and the cycles counted by the local client, so a batch isn't executed again before its request.
+///
+pub struct NetworkBackend {
+    client: NetworkClient,
+    local: ProverClient,
+}
+
+impl NetworkBackend {
+    /// `rpc_url` replaces `PROVER_NETWORK_RPC` and the Succinct RPC.
+    pub fn new(private_key: &str, rpc_url: Option<&str>) -> Result<Self, String> {
+        // The SDK panics on an invalid key.
+        match hex::decode(private_key.trim_start_matches("0x")) {
+            Ok(key) if key.len() == 32 => {}
+            _ => return Err("The network private key must be 32 hex-encoded bytes".to_string()),
+        }
+        let mut client = NetworkClient::new(private_key);
+        if let Some(rpc_url) = rpc_url {
+            // The requests are relative to the base URL, it has to end with a slash.
+            let mut url = twirp::url::Url::parse(rpc_url)
+                .map_err(|err| format!("Invalid network RPC URL: {}", err))?;
+            if !url.path().ends_with('/') {
+                url.set_path(&format!("{}/", url.path()));
+            }
+            client.rpc = twirp::Client::new(url, twirp::reqwest::Client::new(), vec![])
+                .map_err(|err| format!("Invalid network RPC URL: {}", err))?;
+        }
+        Ok(Self { client, local: ProverClient::local() })
+    }
+}
+
+impl ProverBackend for NetworkBackend {
+    fn setup(&self, elf: &[u8]) -> (SP1ProvingKey, SP1VerifyingKey) {
+        self.local.setup(elf)
+    }
+
+    fn cycles(&self, elf: &[u8], batch: &ProvingBatch, max_cycles: u64) -> Result<u64, String> {
+        ProverBackend::cycles(&self.local, elf, batch, max_cycles)
+    }
+
+    fn prove(&self, pk: &SP1ProvingKey, batch: &ProvingBatch, system: ProofSystem) -> Result<SP1ProofWithPublicValues, String> {
+        let mode = match system {
+            ProofSystem::Plonk => ProofMode::Plonk,
+            ProofSystem::Groth16 => ProofMode::Groth16,
+        };
+        let proof_id = block_on(self.client.create_proof(&pk.elf, &stdin(batch), mode, SP1_CIRCUIT_VERSION))
+            .map_err(|err| format!("Failed to request the proof: {}", err))?;
+
+        // The network unclaims the request once its deadline is passed.
+        let mut errors = 0;
+        loop {
+            match block_on(self.client.get_proof_status(&proof_id)) {
+                Ok((status, proof)) => {
+                    errors = 0;
+                    match status.status() {
+                        ProofStatus::ProofFulfilled => {
+                            return proof.ok_or(format!("Proof {} fulfilled without a proof", proof_id));
+                        }
+                        ProofStatus::ProofUnclaimed => {
+                            return Err(format!("Proof {} unclaimed: {}", proof_id, status.unclaim_description()));
+                        }
+                        _ => {}
+                    }
+                }
+                Err(err) => {
+                    errors += 1;
+                    if errors == NETWORK_MAX_ERRORS {
+                        return Err(format!("Failed to get the status of proof {}: {}", proof_id, err));
+                    }
+                }
+            }
+            std::thread::sleep(NETWORK_POLL_INTERVAL);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase", tag = "backend")]
+pub enum BackendConfig {
+    /// Proves on the CPU of the server.
+    #[default]
+    Local,
+    /// Mock proofs of the natively executed batches, accepted only with the dummy verifier.
+    Mock,
+    /// Requests the proofs from the Succinct prover network, the cycles are still counted locally.
+    Network(NetworkConfig),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Hex-encoded key of the requester account, `SP1_PRIVATE_KEY` if it's not set.
+    pub private_key: Option<String>,
+    /// RPC of the prover network, `PROVER_NETWORK_RPC` or the Succinct one if it's not set.
+    pub rpc_url: Option<String>,
+}
+
+impl BackendConfig {
+    pub fn backend(&self) -> Result<Box<dyn ProverBackend>, String> {
+        match self {
+            BackendConfig::Local => Ok(Box::new(ProverClient::local())),
+            BackendConfig::Mock => Ok(Box::new(MockBackend::new())),
+            BackendConfig::Network(network) => {
+                // The environment is only read, the settings are passed to the SDK client.
+                let private_key = network.private_key.clone()
+                    .or_else(|| std::env::var("SP1_PRIVATE_KEY").ok())
+                    .ok_or("The network backend requires a private key".to_string())?;
+                Ok(Box::new(NetworkBackend::new(&private_key, network.rpc_url.as_deref())?))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use super::*;
+
+    #[test]
+    fn mock_cycles() {
+        // CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN
+        let echo = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+        let mut batch = ProvingBatch::default();
+        batch.push(&echo, vec![1, 2]);
+        batch.push(&echo, vec![3]);
+        let cycles = 2 * (MOCK_CALL_CYCLES + 7 * MOCK_STEP_CYCLES);
+        let backend = MockBackend::new();
+        assert_eq!(backend.cycles(&[], &batch, u64::MAX), Ok(cycles));
+        assert_eq!(backend.cycles(&[], &batch, cycles), Ok(cycles));
+        assert!(backend.cycles(&[], &batch, cycles - 1).is_err());
+
+        // JUMPDEST PUSH0 JUMP, never ends.
+        let mut batch = ProvingBatch::default();
+        batch.push(&[0x5b, 0x5f, 0x56], vec![]);
+        assert!(backend.cycles(&[], &batch, 1_000_000_000).is_err());
+    }
+
+    #[test]
+    fn network_rpc_url() {
+        assert!(NetworkBackend::new("0x11", None).is_err());
+
+        // Stand-in of the prover network, it fails the first request after recording its line.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // Without the trailing slash of a base URL.
+        let rpc_url = format!("http://{}/twirp", listener.local_addr().unwrap());
+        let (sender, receiver) = mpsc::channel();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line).unwrap();
+            let _ = stream.write_all(b"HTTP/1.1 500 Internal Server Error\r\ncontent-length: 0\r\n\r\n");
+            sender.send(line).unwrap();
+        });
+
+        let backend = NetworkBackend::new(&format!("0x{}", "11".repeat(32)), Some(&rpc_url)).unwrap();
+        // The request fails with the stand-in, only its line matters.
+        assert!(block_on(backend.client.get_nonce()).is_err());
+
+        let line = receiver.recv_timeout(Duration::from_secs(120)).unwrap();
+        assert!(line.starts_with("POST /twirp/network.NetworkService/GetNonce "), "{}", line);
+    }
+}
@@ -1,5 +0,0 @@
-use sp1_helper::build_program_with_args;
-
-fn main() {
-    build_program_with_args("../program", Default::default())
-}
@@ -0,0 +1,303 @@
+//! Admin routes to inspect the jobs and batches and to manage the batches, they require the
+//! admin token of the config in the [`ADMIN_TOKEN_HEADER`] header instead of an API key.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use axum::{
+    extract::{Path, Query, Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::Response,
+    routing::{get, post},
+    Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use utoipa::{IntoParams, ToSchema};
+use web3::types::{Bytes, H256};
+use solc_zkmod_api::{JobStatus, Submission};
+use solc_zkmod_lib::merkle::MerkleTree;
+use crate::batcher::{Batch, BatchStatus};
+use crate::state::AppState;
+
+pub const ADMIN_TOKEN_HEADER: &str = "x-admin-token";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct JobFilter {
+    pub status: Option<JobStatus>,
+    pub chain_id: Option<u64>,
+    pub batch_id: Option<u64>,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[serde(rename_all = "camelCase")]
+#[into_params(parameter_in = Query)]
+pub struct BatchFilter {
+    pub status: Option<BatchStatus>,
+    /// Batches with calls of the chain.
+    pub chain_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct JobSummary {
+    pub id: String,
+    pub chain_id: u64,
+    /// `None` if all the calls were proven before.
+    pub batch_id: Option<u64>,
+    pub status: JobStatus,
+    pub transactions: usize,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchSummary {
+    pub id: u64,
+    pub status: BatchStatus,
+    pub chains: Vec<u64>,
+    pub jobs: Vec<String>,
+    pub calls: usize,
+    pub error: Option<String>,
+    pub submissions: BTreeMap<u64, Submission>,
+}
+
+/// Batch with its requests and Merkle tree.
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BatchDump {
+    #[serde(flatten)]
+    pub summary: BatchSummary,
+    pub requests: Vec<RequestDump>,
+    /// Leaves of the provable requests, in the order of the requests.
+    #[schema(value_type = Vec<String>)]
+    pub leaves: Vec<H256>,
+    /// `None` if no request is provable.
+    #[schema(value_type = Option<String>)]
+    pub root: Option<H256>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestDump {
+    #[schema(value_type = String)]
+    pub code_hash: H256,
+    #[schema(value_type = String)]
+    pub calldata: Bytes,
+    /// `None` if the request can't be proven.
+    #[schema(value_type = Option<String>)]
+    pub leaf: Option<H256>,
+    /// 1 if the call reverted.
+    pub status: Option<u8>,
+    #[schema(value_type = Option<String>)]
+    pub output: Option<Bytes>,
+}
+
+fn summary(id: u64, batch: &Batch) -> BatchSummary {
+    BatchSummary {
+        id,
+        status: batch.status,
+        chains: batch.chains.iter().copied().collect(),
+        jobs: batch.jobs.clone(),
+        calls: batch.requests.requests.len(),
+        error: batch.error.clone(),
+        submissions: batch.submissions.clone(),
+    }
+}
+
+// Handler for /admin/jobs
+#[utoipa::path(
+    get,
+    path = "/admin/jobs",
+    params(JobFilter),
+    responses((status = 200, body = Vec<JobSummary>)),
+    security(("admin_token" = [])),
+)]
+async fn list_jobs(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<JobFilter>,
+) -> Json<Vec<JobSummary>> {
+    // The jobs are locked before the batches, as in the handlers.
+    let jobs = state.submitted_proofs.lock().unwrap();
+    let mut summaries = state.batcher.with_batches(|batches| {
+        jobs.iter()
+            .filter(|(_, job)| filter.chain_id.is_none() || filter.chain_id == Some(job.chain_id))
+            .filter(|(_, job)| filter.batch_id.is_none() || filter.batch_id == job.batch_id)
+            .map(|(id, job)| JobSummary {
+                id: id.clone(),
+                chain_id: job.chain_id,
+                batch_id: job.batch_id,
+                status: job.status(job.batch_id.and_then(|batch_id| batches.get(&batch_id))),
+                transactions: job.transactions.len(),
+            })
+            .filter(|summary| filter.status.is_none() || filter.status == Some(summary.status))
+            .collect::<Vec<_>>()
+    });
+    drop(jobs);
+    summaries.sort_by(|a, b| (a.batch_id, &a.id).cmp(&(b.batch_id, &b.id)));
+    Json(summaries)
+}
+
+// Handler for /admin/batches
+#[utoipa::path(
+    get,
+    path = "/admin/batches",
+    params(BatchFilter),
+    responses((status = 200, body = Vec<BatchSummary>)),
+    security(("admin_token" = [])),
+)]
+async fn list_batches(
+    State(state): State<Arc<AppState>>,
+    Query(filter): Query<BatchFilter>,
+) -> Json<Vec<BatchSummary>> {
+    Json(state.batcher.with_batches(|batches| {
+        batches.iter()
+            .filter(|(_, batch)| filter.status.is_none() || filter.status == Some(batch.status))
+            .filter(|(_, batch)| match filter.chain_id {
+                Some(chain_id) => batch.chains.contains(&chain_id),
+                None => true,
+            })
+            .map(|(id, batch)| summary(*id, batch))
+            .collect()
+    }))
+}
+
+// Handler for /admin/batches/{id}, the batch is executed to build its tree
+#[utoipa::path(
+    get,
+    path = "/admin/batches/{id}",
+    params(("id" = u64, Path, description = "Batch id")),
+    responses(
+        (status = 200, body = BatchDump),
+        (status = 404, description = "Unknown batch", body = String),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn get_batch(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BatchDump>, (StatusCode, String)> {
+    let (summary, requests) = state.batcher
+        .with_batch(id, |batch| (summary(id, batch), batch.requests.clone()))
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown batch {}", id)))?;
+    let executed = tokio::task::spawn_blocking({
+        let requests = requests.clone();
+        move || requests.execute()
+    })
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+
+    let leaves = executed.iter().flatten().map(|(leaf, _)| *leaf).collect::<Vec<_>>();
+    let root = (!leaves.is_empty()).then(|| H256(MerkleTree::new(leaves.clone()).root()));
+    let requests = requests.requests.into_iter().zip(executed).map(|(request, executed)| RequestDump {
+        code_hash: H256(request.code_hash),
+        calldata: Bytes(request.calldata),
+        leaf: executed.as_ref().map(|(leaf, _)| H256(*leaf)),
+        status: executed.as_ref().map(|(_, outcome)| outcome.status as u8),
+        output: executed.map(|(_, outcome)| Bytes(outcome.output)),
+    }).collect();
+    Ok(Json(BatchDump {
+        summary,
+        requests,
+        leaves: leaves.into_iter().map(H256).collect(),
+        root,
+    }))
+}
+
+/// Unknown batches are 404, the changes not allowed in the batch status are 409.
+fn batch_action(state: &AppState, id: u64, action: impl FnOnce() -> Result<(), String>) -> Result<Json<BatchSummary>, (StatusCode, String)> {
+    if state.batcher.with_batch(id, |_| ()).is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("Unknown batch {}", id)));
+    }
+    action().map_err(|err| (StatusCode::CONFLICT, err))?;
+    let summary = state.batcher.with_batch(id, |batch| summary(id, batch)).unwrap();
+    Ok(Json(summary))
+}
+
+// Handler for /admin/batches/{id}/retry, proves a failed batch again, or resubmits the root of a
+// proven batch to the chains where the submission failed
+#[utoipa::path(
+    post,
+    path = "/admin/batches/{id}/retry",
+    params(("id" = u64, Path, description = "Batch id")),
+    responses(
+        (status = 200, body = BatchSummary),
+        (status = 404, description = "Unknown batch", body = String),
+        (status = 409, description = "Nothing failed in the batch", body = String),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn retry_batch(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BatchSummary>, (StatusCode, String)> {
+    batch_action(&state, id, || state.batcher.retry(id))
+}
+
+// Handler for /admin/batches/{id}/cancel, fails an open or queued batch with its jobs
+#[utoipa::path(
+    post,
+    path = "/admin/batches/{id}/cancel",
+    params(("id" = u64, Path, description = "Batch id")),
+    responses(
+        (status = 200, body = BatchSummary),
+        (status = 404, description = "Unknown batch", body = String),
+        (status = 409, description = "The batch is proving or done", body = String),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn cancel_batch(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BatchSummary>, (StatusCode, String)> {
+    batch_action(&state, id, || state.batcher.cancel(id))
+}
+
+// Handler for /admin/batches/{id}/close, queues an open batch for proving right away
+#[utoipa::path(
+    post,
+    path = "/admin/batches/{id}/close",
+    params(("id" = u64, Path, description = "Batch id")),
+    responses(
+        (status = 200, body = BatchSummary),
+        (status = 404, description = "Unknown batch", body = String),
+        (status = 409, description = "The batch is not open", body = String),
+    ),
+    security(("admin_token" = [])),
+)]
+async fn close_batch(
+    Path(id): Path<u64>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BatchSummary>, (StatusCode, String)> {
+    batch_action(&state, id, || state.batcher.force_close(id))
+}
+
+/// The admin routes are disabled without a token in the config.
+async fn authenticate_admin(
+    State(token): State<Option<Arc<str>>>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let token = token.ok_or((StatusCode::FORBIDDEN, "The admin routes are disabled".to_string()))?;
+    let provided = request.headers()
+        .get(ADMIN_TOKEN_HEADER)
+        .and_then(|token| token.to_str().ok())
+        .ok_or((StatusCode::UNAUTHORIZED, format!("Missing {} header", ADMIN_TOKEN_HEADER)))?;
+    // The hashes are compared, so the time of the comparison doesn't depend on the token.
+    if Sha256::digest(provided.as_bytes()) != Sha256::digest(token.as_bytes()) {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid admin token".to_string()));
+    }
+    Ok(next.run(request).await)
+}
+
+pub fn router(admin_token: Option<String>) -> Router<Arc<AppState>> {
+    Router::new()
+        .route("/jobs", get(list_jobs))
+        .route("/batches", get(list_batches))
+        .route("/batches/:id", get(get_batch))
+        .route("/batches/:id/retry", post(retry_batch))
+        .route("/batches/:id/cancel", post(cancel_batch))
+        .route("/batches/:id/close", post(close_batch))
+        .layer(middleware::from_fn_with_state(admin_token.map(Arc::from), authenticate_admin))
+}
@@ -0,0 +1,152 @@
+//! API-key authentication and per-key limits.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use crate::state::AppState;
+use crate::store::StoreData;
+
+pub use solc_zkmod_api::API_KEY_HEADER;
+
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Key of the authenticated request, available to the handlers as an extension.
+#[derive(Debug, Clone)]
+pub struct AuthenticatedKey(pub String);
+
+/// Counts the requests of every key in fixed one-minute windows.
+#[derive(Default)]
+pub struct RateLimiter {
+    windows: Mutex<HashMap<String, (Instant, u32)>>,
+}
+
+impl RateLimiter {
+    /// Counts the request, `false` if the key exceeded the `limit` in the current window.
+    pub fn check(&self, key: &str, limit: u32) -> bool {
+        let now = Instant::now();
+        let mut windows = self.windows.lock().unwrap();
+        let (start, count) = windows.entry(key.to_string()).or_insert((now, 0));
+        if now.duration_since(*start) >= RATE_LIMIT_WINDOW {
+            *start = now;
+            *count = 0;
+        }
+        if *count >= limit {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+}
+
+pub async fn authenticate(
+    State(state): State<Arc<AppState>>,
+    mut request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let key = request.headers()
+        .get(API_KEY_HEADER)
+        .and_then(|key| key.to_str().ok())
+        .ok_or((StatusCode::UNAUTHORIZED, format!("Missing {} header", API_KEY_HEADER)))?
+        .to_string();
+    let rate_limit = state.store
+        .read(|data| data.api_keys.get(&key).map(|api_key| api_key.rate_limit))
+        .ok_or((StatusCode::UNAUTHORIZED, "Unknown API key".to_string()))?;
+    if let Some(limit) = rate_limit {
+        if !state.rate_limiter.check(&key, limit) {
+            return Err((StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded".to_string()));
+        }
+    }
+
+    request.extensions_mut().insert(AuthenticatedKey(key));
+    Ok(next.run(request).await)
+}
+
+/// Fails if the key has no cycles left, checked before the calls are executed.
+pub fn check_quota(data: &StoreData, key: &str) -> Result<(), (StatusCode, String)> {
+    let api_key = data.api_keys.get(key)
+        .ok_or((StatusCode::UNAUTHORIZED, "Unknown API key".to_string()))?;
+    match api_key.cycle_quota {
+        Some(quota) if api_key.usage.cycles >= quota => Err((StatusCode::FORBIDDEN, format!(
+            "Cycle quota exhausted: {} of {} used",
+            api_key.usage.cycles,
+            quota,
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// Accounts the proven calls and their cycles, fails if they exceed the cycle quota of the key.
+pub fn charge(data: &mut StoreData, key: &str, calls: u64, cycles: u64) -> Result<(), (StatusCode, String)> {
+    charge_execution(data, key, cycles)?;
+    let usage = &mut data.api_keys.get_mut(key).unwrap().usage;
+    usage.proof_requests += 1;
+    usage.calls += calls;
+    Ok(())
+}
+
+/// Accounts the cycles of the executions without a proof request, as the estimates.
+pub fn charge_execution(data: &mut StoreData, key: &str, cycles: u64) -> Result<(), (StatusCode, String)> {
+    let api_key = data.api_keys.get_mut(key)
+        .ok_or((StatusCode::UNAUTHORIZED, "Unknown API key".to_string()))?;
+    let total_cycles = api_key.usage.cycles.saturating_add(cycles);
+    if api_key.cycle_quota.is_some_and(|quota| total_cycles > quota) {
+        return Err((StatusCode::FORBIDDEN, format!(
+            "Cycle quota exceeded: {} cycles requested, {} of {} used",
+            cycles,
+            api_key.usage.cycles,
+            api_key.cycle_quota.unwrap(),
+        )));
+    }
+    api_key.usage.cycles = total_cycles;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{ApiKey, Usage};
+
+    fn data(cycle_quota: Option<u64>, cycles: u64) -> StoreData {
+        let mut data = StoreData::default();
+        data.api_keys.insert("key".to_string(), ApiKey {
+            name: "test".to_string(),
+            rate_limit: None,
+            cycle_quota,
+            usage: Usage { cycles, ..Default::default() },
+        });
+        data
+    }
+
+    #[test]
+    fn quota() {
+        assert!(check_quota(&data(None, 100), "key").is_ok());
+        assert!(check_quota(&data(Some(100), 99), "key").is_ok());
+        assert_eq!(check_quota(&data(Some(100), 100), "key").unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(check_quota(&data(Some(100), 0), "other").unwrap_err().0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[test]
+    fn charge_cycles() {
+        let mut data = data(Some(100), 50);
+        charge(&mut data, "key", 2, 50).unwrap();
+        let usage = &data.api_keys["key"].usage;
+        assert_eq!((usage.proof_requests, usage.calls, usage.cycles), (1, 2, 100));
+        assert_eq!(charge(&mut data, "key", 1, 1).unwrap_err().0, StatusCode::FORBIDDEN);
+        assert_eq!(data.api_keys["key"].usage.cycles, 100);
+    }
+
+    #[test]
+    fn charge_estimate_cycles() {
+        let mut data = data(Some(100), 50);
+        charge_execution(&mut data, "key", 30).unwrap();
+        let usage = &data.api_keys["key"].usage;
+        assert_eq!((usage.proof_requests, usage.calls, usage.cycles), (0, 0, 80));
+        assert_eq!(charge_execution(&mut data, "key", 21).unwrap_err().0, StatusCode::FORBIDDEN);
+    }
+}
@@ -0,0 +1,583 @@
+//! Collects the requests of different jobs into batches and proves them with a pool of workers.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use alloy_sol_types::SolType;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::SP1ProofWithPublicValues;
+use tokio::sync::{broadcast, Semaphore};
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
+use tracing::Instrument;
+use utoipa::ToSchema;
+use solc_zkmod_api::Submission;
+use solc_zkmod_calldata::Witness;
+use solc_zkmod_lib::batch::ProvingBatch;
+use solc_zkmod_lib::prover::prover::Prover;
+use solc_zkmod_lib::merkle::MerkleTree;
+use solc_zkmod_lib::{keccak256, ExecutionStatus, PublicValuesStruct};
+use crate::metrics::METRICS;
+use crate::store::Store;
+use crate::submitter::Submitter;
+
+const BATCH_RECORDS: &str = "batches";
+
+/// When the open batch is closed and sent to proving.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BatchPolicy {
+    /// Closes the batch once it has this many calls.
+    pub max_calls: usize,
+    /// Closes the batch this many seconds after its first call.
+    pub max_wait_secs: u64,
+    /// Batches the calls of every chain separately, otherwise a batch root is submitted to all
+    /// the chains of its calls.
+    pub per_chain: bool,
+}
+
+impl Default for BatchPolicy {
+    fn default() -> Self {
+        Self {
+            max_calls: 16,
+            max_wait_secs: 60,
+            per_chain: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchStatus {
+    /// Accepts new calls.
+    Open,
+    /// Waits for a free worker.
+    Queued,
+    Proving,
+    Proven,
+    Failed,
+}
+
+/// Saved in the store on every change, so it's resumed after a restart.
+#[derive(Serialize, Deserialize)]
+pub struct Batch {
+    pub requests: ProvingBatch,
+    /// Ids of the jobs with calls in the batch.
+    pub jobs: Vec<String>,
+    /// Chains to submit the root to.
+    pub chains: BTreeSet<u64>,
+    pub status: BatchStatus,
+    #[serde(skip, default = "Instant::now")]
+    pub opened_at: Instant,
+    pub proof: Option<SP1ProofWithPublicValues>,
+    pub error: Option<String>,
+    /// Submissions to the chains once the batch is proven, empty without a submitter.
+    pub submissions: BTreeMap<u64, Submission>,
+    /// Set once the batch is proven, executed again when it's recovered.
+    #[serde(skip)]
+    pub executed: Option<Arc<ExecutedBatch>>,
+}
+
+/// Native execution of a proven batch, kept so the witnesses are served without executing it again.
+pub struct ExecutedBatch {
+    pub root: [u8; 32],
+    /// Leaf and witness of every request, `None` if it can't be proven.
+    pub calls: Vec<Option<([u8; 32], Witness)>>,
+}
+
+impl ExecutedBatch {
+    /// Executes the batch, fails if none of its requests can be proven.
+    pub fn new(requests: &ProvingBatch) -> Result<Self, String> {
+        let executed = requests.execute();
+        let leaves = executed.iter().flatten().map(|(leaf, _)| *leaf).collect::<Vec<_>>();
+        if leaves.is_empty() {
+            return Err("No provable requests in the batch".to_string());
+        }
+        let tree = MerkleTree::new(leaves);
+        let calls = requests.requests.iter().zip(executed).map(|(request, executed)| {
+            let (leaf, outcome) = executed?;
+            Some((leaf, Witness {
+                code_hash: request.code_hash,
+                input_hash: keccak256(request.calldata.as_slice()),
+                reverted: outcome.status == ExecutionStatus::Revert,
+                output: outcome.output,
+                proof: tree.proof(leaf).unwrap(),
+            }))
+        }).collect();
+        Ok(Self { root: tree.root(), calls })
+    }
+
+    /// Merkle proofs of the provable calls by their leaf.
+    fn call_proofs(&self) -> Vec<([u8; 32], ProvenCall)> {
+        self.calls.iter()
+            .flatten()
+            .map(|(leaf, witness)| (*leaf, ProvenCall { root: self.root, proof: witness.proof.clone() }))
+            .collect()
+    }
+}
+
+pub struct Batcher {
+    policy: BatchPolicy,
+    prover: Arc<Prover>,
+    submitter: Option<Arc<Submitter>>,
+    workers: Arc<Semaphore>,
+    batches: Mutex<Batches>,
+    /// Id of every batch that changed its status.
+    events: broadcast::Sender<u64>,
+    proven_calls: Mutex<ProvenCalls>,
+    store: Arc<Store>,
+    /// Proving and submission tasks, waited for on shutdown.
+    tasks: TaskTracker,
+    shutdown: CancellationToken,
+}
+
+/// Merkle proofs of the calls of the proven batches, reused once their root is submitted.
+#[derive(Default)]
+struct ProvenCalls {
+    /// Proofs of every leaf, one for each batch that proved it.
+    proofs: HashMap<[u8; 32], Vec<ProvenCall>>,
+    /// Chains every root is recorded on.
+    submitted: HashMap<[u8; 32], BTreeSet<u64>>,
+}
+
+struct ProvenCall {
+    root: [u8; 32],
+    proof: Vec<[u8; 32]>,
+}
+
+#[derive(Default)]
+struct Batches {
+    next_id: u64,
+    /// Open batch of every chain, or of all the chains under the `None` key.
+    open: HashMap<Option<u64>, u64>,
+    batches: BTreeMap<u64, Batch>,
+}
+
+impl Batcher {
+    pub fn new(
+        policy: BatchPolicy,
+        workers: usize,
+        prover: Arc<Prover>,
+        submitter: Option<Arc<Submitter>>,
+        store: Arc<Store>,
+    ) -> Self {
+        Self {
+            policy,
+            prover,
+            submitter,
+            workers: Arc::new(Semaphore::new(workers)),
+            batches: Mutex::new(Batches::default()),
+            events: broadcast::channel(1024).0,
+            proven_calls: Mutex::new(ProvenCalls::default()),
+            store,
+            tasks: TaskTracker::new(),
+            shutdown: CancellationToken::new(),
+        }
+    }
+
+    ///
+    /// Loads the saved batches. The queued and proving ones are proven again, the roots of the
+    /// proven ones are submitted to the chains where their submission is still pending.
+    ///
+    pub fn recover(self: &Arc<Self>) -> Result<(), String> {
+        let mut resumed = vec![];
+        let mut batches = self.batches.lock().unwrap();
+        for (id, mut batch) in self.store.load_records::<Batch>(BATCH_RECORDS)? {
+            let id = id.parse::<u64>().map_err(|err| format!("Invalid batch id {}: {}", id, err))?;
+            batches.next_id = batches.next_id.max(id + 1);
+            let resume = match batch.status {
+                BatchStatus::Open => {
+                    let key = match self.policy.per_chain {
+                        true => batch.chains.first().copied(),
+                        false => None,
+                    };
+                    // The key may have another open batch after a change of the policy.
+                    match batches.open.contains_key(&key) {
+                        true => true,
+                        false => {
+                            batches.open.insert(key, id);
+                            false
+                        }
+                    }
+                }
+                BatchStatus::Queued | BatchStatus::Proving => true,
+                BatchStatus::Proven => {
+                    let executed = ExecutedBatch::new(&batch.requests)
+                        .map_err(|err| format!("Invalid batch {}: {}", id, err))?;
+                    let mut proven_calls = self.proven_calls.lock().unwrap();
+                    for (leaf, call) in executed.call_proofs() {
+                        for (chain_id, submission) in &batch.submissions {
+                            if matches!(submission, Submission::Submitted { .. }) {
+                                proven_calls.submitted.entry(call.root).or_default().insert(*chain_id);
+                            }
+                        }
+                        proven_calls.proofs.entry(leaf).or_default().push(call);
+                    }
+                    batch.executed = Some(Arc::new(executed));
+                    false
+                }
+                BatchStatus::Failed => false,
+            };
+            if resume {
+                batch.status = BatchStatus::Queued;
+                resumed.push(id);
+            }
+            batches.batches.insert(id, batch);
+        }
+
+        for id in &resumed {
+            let batch = &batches.batches[id];
+            self.save(*id, batch);
+            let span = tracing::info_span!("batch", batch_id = id, jobs = ?batch.jobs);
+            span.in_scope(|| tracing::info!("Batch resumed"));
+            self.prove(*id, span);
+        }
+        let pending = batches.batches
+            .iter()
+            .filter(|(_, batch)| batch.submissions.values().any(|submission| *submission == Submission::Pending))
+            .filter_map(|(id, batch)| Some((*id, batch.proof.clone()?, tracing::info_span!("batch", batch_id = id, jobs = ?batch.jobs))))
+            .collect::<Vec<_>>();
+        drop(batches);
+
+        if let Some(submitter) = &self.submitter {
+            for (id, proof, span) in pending {
+                span.in_scope(|| self.submit(submitter.clone(), id, proof));
+            }
+        }
+        Ok(())
+    }
+
+    /// Stops closing the batches and proving the queued ones, they are resumed on the next start.
+    pub fn shutdown(&self) {
+        self.shutdown.cancel();
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutdown.is_cancelled()
+    }
+
+    pub async fn shutting_down(&self) {
+        self.shutdown.cancelled().await
+    }
+
+    /// Waits for the proofs and submissions in progress, `false` if they didn't finish in time.
+    pub async fn drain(&self, timeout: Duration) -> bool {
+        self.shutdown();
+        self.tasks.close();
+        tokio::time::timeout(timeout, self.tasks.wait()).await.is_ok()
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<u64> {
+        self.events.subscribe()
+    }
+
+    ///
+    /// Adds the requests of the chain to the open batch, closing it if it's full.
+    /// Returns the batch id and the index of the first added request in the batch.
+    ///
+    pub fn add(self: &Arc<Self>, chain_id: u64, job_id: &str, requests: &ProvingBatch) -> (u64, usize) {
+        let key = self.policy.per_chain.then_some(chain_id);
+        let mut batches = self.batches.lock().unwrap();
+        let id = match batches.open.get(&key) {
+            Some(id) => *id,
+            None => {
+                let id = batches.next_id;
+                batches.next_id += 1;
+                batches.open.insert(key, id);
+                batches.batches.insert(id, Batch {
+                    requests: ProvingBatch::default(),
+                    jobs: vec![],
+                    chains: BTreeSet::new(),
+                    status: BatchStatus::Open,
+                    opened_at: Instant::now(),
+                    proof: None,
+                    error: None,
+                    submissions: BTreeMap::new(),
+                    executed: None,
+                });
+                id
+            }
+        };
+        let batch = batches.batches.get_mut(&id).unwrap();
+        batch.chains.insert(chain_id);
+        if !batch.jobs.iter().any(|job| job == job_id) {
+            batch.jobs.push(job_id.to_string());
+        }
+        let offset = batch.requests.append(requests);
+        self.save(id, batch);
+        if batch.requests.requests.len() >= self.policy.max_calls {
+            self.close(&mut batches, key);
+        }
+        (id, offset)
+    }
+
+    /// Calls of the open batch the calls of the chain are added to.
+    pub fn open_calls(&self, chain_id: u64) -> usize {
+        let key = self.policy.per_chain.then_some(chain_id);
+        let batches = self.batches.lock().unwrap();
+        batches.open
+            .get(&key)
+            .map_or(0, |id| batches.batches[id].requests.requests.len())
+    }
+
+    pub fn with_batch<R>(&self, id: u64, f: impl FnOnce(&Batch) -> R) -> Option<R> {
+        self.batches.lock().unwrap().batches.get(&id).map(f)
+    }
+
+    /// Merkle proof of the call with the leaf, if it's proven in a batch whose root is recorded on the chain.
+    pub fn proven_call(&self, leaf: &[u8; 32], chain_id: u64) -> Option<Vec<[u8; 32]>> {
+        let proven_calls = self.proven_calls.lock().unwrap();
+        proven_calls.proofs
+            .get(leaf)?
+            .iter()
+            .find(|call| proven_calls.submitted.get(&call.root).is_some_and(|chains| chains.contains(&chain_id)))
+            .map(|call| call.proof.clone())
+    }
+
+    pub fn with_batches<R>(&self, f: impl FnOnce(&BTreeMap<u64, Batch>) -> R) -> R {
+        f(&self.batches.lock().unwrap().batches)
+    }
+
+    /// Closes the open batches once they are waiting longer than the policy allows.
+    pub async fn run(self: Arc<Self>) {
+        let max_wait = Duration::from_secs(self.policy.max_wait_secs);
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = self.shutdown.cancelled() => return,
+            }
+            let mut batches = self.batches.lock().unwrap();
+            let expired = batches.open
+                .iter()
+                .filter(|(_, id)| batches.batches[id].opened_at.elapsed() >= max_wait)
+                .map(|(key, _)| *key)
+                .collect::<Vec<_>>();
+            for key in expired {
+                self.close(&mut batches, key);
+            }
+        }
+    }
+
+    /// Closes the open batch without waiting for the policy.
+    pub fn force_close(self: &Arc<Self>, id: u64) -> Result<(), String> {
+        let mut batches = self.batches.lock().unwrap();
+        let key = batches.open
+            .iter()
+            .find(|(_, open)| **open == id)
+            .map(|(key, _)| *key)
+            .ok_or(format!("Batch {} is not open", id))?;
+        self.close(&mut batches, key);
+        Ok(())
+    }
+
+    /// Fails the open or queued batch, with all its jobs.
+    pub fn cancel(&self, id: u64) -> Result<(), String> {
+        let mut batches = self.batches.lock().unwrap();
+        let status = batches.batches.get(&id).ok_or(format!("Unknown batch {}", id))?.status;
+        match status {
+            BatchStatus::Open => batches.open.retain(|_, open| *open != id),
+            BatchStatus::Queued => {}
+            status => return Err(format!("Batch {} is {:?}, only open and queued batches are cancelled", id, status)),
+        }
+        let batch = batches.batches.get_mut(&id).unwrap();
+        batch.status = BatchStatus::Failed;
+        batch.error = Some("Cancelled".to_string());
+        self.save(id, batch);
+        drop(batches);
+        let _ = self.events.send(id);
+        tracing::info!(batch_id = id, "Batch cancelled");
+        Ok(())
+    }
+
+    /// Proves the failed batch again, or submits the root of the proven one to the chains where it failed.
+    pub fn retry(self: &Arc<Self>, id: u64) -> Result<(), String> {
+        let mut batches = self.batches.lock().unwrap();
+        let batch = batches.batches.get_mut(&id).ok_or(format!("Unknown batch {}", id))?;
+        let span = tracing::info_span!("batch", batch_id = id, jobs = ?batch.jobs);
+        match batch.status {
+            BatchStatus::Failed => {
+                batch.status = BatchStatus::Queued;
+                batch.error = None;
+                self.save(id, batch);
+                drop(batches);
+                let _ = self.events.send(id);
+                span.in_scope(|| tracing::info!("Batch retried"));
+                self.prove(id, span);
+            }
+            BatchStatus::Proven => {
+                let submitter = self.submitter.clone().ok_or("The submitter is disabled".to_string())?;
+                let mut failed = 0;
+                for submission in batch.submissions.values_mut() {
+                    if let Submission::Failed { .. } = submission {
+                        *submission = Submission::Pending;
+                        failed += 1;
+                    }
+                }
+                if failed == 0 {
+                    return Err(format!("Batch {} has no failed submissions", id));
+                }
+                let proof = batch.proof.clone().unwrap();
+                self.save(id, batch);
+                drop(batches);
+                let _ = self.events.send(id);
+                span.in_scope(|| {
+                    tracing::info!(failed, "Submissions retried");
+                    self.submit(submitter, id, proof);
+                });
+            }
+            status => return Err(format!("Batch {} is {:?}, only failed batches and submissions are retried", id, status)),
+        }
+        Ok(())
+    }
+
+    fn close(self: &Arc<Self>, batches: &mut Batches, key: Option<u64>) {
+        let Some(id) = batches.open.remove(&key) else {
+            return;
+        };
+        let batch = batches.batches.get_mut(&id).unwrap();
+        batch.status = BatchStatus::Queued;
+        METRICS.batch_calls.observe(batch.requests.requests.len() as f64);
+        self.save(id, batch);
+        let _ = self.events.send(id);
+        // The job ids are in the span, so the logs of the batch are found by any of its jobs.
+        let span = tracing::info_span!("batch", batch_id = id, jobs = ?batch.jobs);
+        span.in_scope(|| tracing::info!(calls = batch.requests.requests.len(), "Batch queued"));
+        self.prove(id, span);
+    }
+
+    fn prove(self: &Arc<Self>, id: u64, span: tracing::Span) {
+        let batcher = self.clone();
+        self.tasks.spawn(async move {
+            let _permit = batcher.workers.clone().acquire_owned().await.unwrap();
+            // The queued batch stays saved until the next start.
+            if batcher.is_shutting_down() {
+                return;
+            }
+            let requests = batcher.update(id, |batch| {
+                // Cancelled while it was queued.
+                if batch.status != BatchStatus::Queued {
+                    return None;
+                }
+                batch.status = BatchStatus::Proving;
+                Some(batch.requests.clone())
+            });
+            let Some(requests) = requests else {
+                return;
+            };
+            tracing::info!("Proving");
+
+            let prover = batcher.prover.clone();
+            let started_at = Instant::now();
+            let proof = tokio::task::spawn_blocking(move || {
+                let proof = prover.prove(&requests)?;
+                // The witnesses are built from the native execution, so it must match the proven one.
+                let executed = ExecutedBatch::new(&requests)?;
+                let public_values = PublicValuesStruct::abi_decode(proof.public_values.as_slice(), false)
+                    .map_err(|err| format!("Invalid public values: {}", err))?;
+                if public_values._merkleRoot.0 != executed.root {
+                    return Err("Proven root differs from the execution".to_string());
+                }
+                Ok((proof, executed))
+            })
+                .await
+                .unwrap_or_else(|err| Err(err.to_string()));
+            let duration = started_at.elapsed().as_secs_f64();
+            let result = if proof.is_ok() { "ok" } else { "error" };
+            METRICS.proving_duration.with_label_values(&[result]).observe(duration);
+            match &proof {
+                Ok(_) => tracing::info!(duration, "Batch proven"),
+                Err(err) => tracing::error!(duration, error = %err, "Proving failed"),
+            }
+
+            let submit = batcher.submitter.is_some();
+            let proof = proof.map(|(proof, executed)| {
+                let mut proven_calls = batcher.proven_calls.lock().unwrap();
+                for (leaf, call) in executed.call_proofs() {
+                    proven_calls.proofs.entry(leaf).or_default().push(call);
+                }
+                (proof, Arc::new(executed))
+            });
+            let proof = batcher.update(id, |batch| match proof {
+                Ok((proof, executed)) => {
+                    batch.status = BatchStatus::Proven;
+                    batch.proof = Some(proof.clone());
+                    batch.executed = Some(executed);
+                    if submit {
+                        for chain_id in &batch.chains {
+                            batch.submissions.insert(*chain_id, Submission::Pending);
+                        }
+                    }
+                    Some(proof)
+                }
+                Err(err) => {
+                    batch.status = BatchStatus::Failed;
+                    batch.error = Some(err);
+                    None
+                }
+            });
+            drop(_permit);
+
+            if let (Some(proof), Some(submitter)) = (proof, batcher.submitter.clone()) {
+                batcher.submit(submitter, id, proof);
+            }
+        }.instrument(span));
+    }
+
+    fn submit(self: &Arc<Self>, submitter: Arc<Submitter>, id: u64, proof: SP1ProofWithPublicValues) {
+        let chains = self.with_batch(id, |batch| {
+            batch.submissions
+                .iter()
+                .filter(|(_, submission)| **submission == Submission::Pending)
+                .map(|(chain_id, _)| *chain_id)
+                .collect::<Vec<_>>()
+        }).unwrap();
+        let root = match PublicValuesStruct::abi_decode(proof.public_values.as_slice(), false) {
+            Ok(public_values) => public_values._merkleRoot.0,
+            Err(err) => {
+                let error = format!("Invalid public values: {}", err);
+                tracing::error!(error = %error, "Can't submit the root");
+                return self.update(id, |batch| batch.submissions.values_mut().for_each(|submission| {
+                    *submission = Submission::Failed { error: error.clone() }
+                }));
+            }
+        };
+
+        // Chains are submitted to concurrently, the transactions of a chain are serialized by the submitter.
+        for chain_id in chains {
+            let batcher = self.clone();
+            let submitter = submitter.clone();
+            let proof = proof.bytes();
+            self.tasks.spawn(async move {
+                let submission = match submitter.submit(chain_id, root, proof).await {
+                    Ok(tx_hash) => {
+                        tracing::info!(tx_hash = ?tx_hash, "Root submitted");
+                        batcher.proven_calls.lock().unwrap().submitted.entry(root).or_default().insert(chain_id);
+                        Submission::Submitted { tx_hash }
+                    }
+                    Err(error) => {
+                        tracing::error!(error = %error, "Submission failed");
+                        Submission::Failed { error }
+                    }
+                };
+                batcher.update(id, |batch| batch.submissions.insert(chain_id, submission));
+            }.instrument(tracing::info_span!("submission", chain_id)));
+        }
+    }
+
+    fn update<R>(&self, id: u64, f: impl FnOnce(&mut Batch) -> R) -> R {
+        let mut batches = self.batches.lock().unwrap();
+        let batch = batches.batches.get_mut(&id).unwrap();
+        let result = f(batch);
+        self.save(id, batch);
+        drop(batches);
+        let _ = self.events.send(id);
+        result
+    }
+
+    fn save(&self, id: u64, batch: &Batch) {
+        if let Err(err) = self.store.save_record(BATCH_RECORDS, &id.to_string(), batch) {
+            tracing::error!(batch_id = id, error = %err, "Failed to save the batch");
+        }
+    }
+}
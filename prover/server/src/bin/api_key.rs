@@ -0,0 +1,70 @@
+//! Manages the API keys in the server store, run it while the server is stopped:
+//! ```shell
+//! cargo run --release --bin api-key -- add --name team --rate-limit 60 --cycle-quota 100000000000
+//! cargo run --release --bin api-key -- list
+//! ```
+
+use std::path::PathBuf;
+use clap::{Parser, Subcommand};
+use rand::RngCore;
+use solc_zkmod_server::store::{ApiKey, Store, Usage};
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Storage directory of the server.
+    #[arg(long, env = "STORAGE_PATH", default_value = "storage")]
+    storage: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generates a new key.
+    Add {
+        #[arg(long)]
+        name: String,
+        /// Requests per minute.
+        #[arg(long)]
+        rate_limit: Option<u32>,
+        /// Total zkVM cycles of the proven requests.
+        #[arg(long)]
+        cycle_quota: Option<u64>,
+    },
+    /// Prints the keys with their usage.
+    List,
+    Remove {
+        key: String,
+    },
+}
+
+fn main() {
+    let args = Args::parse();
+    let store = Store::open(args.storage).expect("Failed to open the store");
+
+    match args.command {
+        Command::Add { name, rate_limit, cycle_quota } => {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            let key = hex::encode(bytes);
+            store.update(|data| data.api_keys.insert(key.clone(), ApiKey {
+                name,
+                rate_limit,
+                cycle_quota,
+                usage: Usage::default(),
+            })).expect("Failed to save the key");
+            println!("{}", key);
+        }
+        Command::List => store.read(|data| {
+            for (key, api_key) in &data.api_keys {
+                println!("{} {:?}", key, api_key);
+            }
+        }),
+        Command::Remove { key } => {
+            let removed = store.update(|data| data.api_keys.remove(&key)).expect("Failed to save the store");
+            if removed.is_none() {
+                println!("Unknown key {}", key);
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+//! Generates an EVM-compatible proof of a batch and a fixture to test its verification on-chain.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin fixture -- --system groth16 --input requests.json --out ../contracts/test/fixtures
+//! ```
+//! where `requests.json` is a list of `{"bytecode": "0x...", "calldata": "0x..."}`.
+//!
+//! With `--mock` the batch is only executed natively, the fixture has no proof and tests the
+//! verification of the requests against a root submitted with the dummy verifier.
+
+use std::path::PathBuf;
+use alloy_sol_types::SolType;
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use sp1_sdk::{HashableKey, ProverClient, SP1Stdin};
+use solc_zkmod_lib::merkle::MerkleTree;
+use solc_zkmod_lib::batch::ProvingBatch;
+use solc_zkmod_lib::prover::prover::{ProofSystem, SOLC_ZKMOD_ELF};
+use solc_zkmod_lib::{keccak256, PublicValuesStruct};
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[arg(long, value_enum, default_value = "groth16")]
+    system: ProofSystem,
+    /// JSON file with the bytecode/calldata pairs to prove.
+    #[arg(long)]
+    input: PathBuf,
+    /// Directory to write the fixture to.
+    #[arg(long)]
+    out: PathBuf,
+    /// Write `mock-fixture.json` without proving the batch.
+    #[arg(long)]
+    mock: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct FixtureRequest {
+    bytecode: String,
+    calldata: String,
+}
+
+/// A fixture that can be used to test the verification of SP1 zkVM proofs inside Solidity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SP1ProofFixture {
+    merkle_root: String,
+    /// Empty in a mock fixture.
+    vkey: String,
+    public_values: String,
+    /// Empty in a mock fixture.
+    proof: String,
+    requests: Vec<RequestFixture>,
+}
+
+/// Arguments of `ComputationOracle.verifyExecution` for a single proven request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestFixture {
+    code_hash: String,
+    input_hash: String,
+    status: u8,
+    output_hash: String,
+    output: String,
+    merkle_proof: Vec<String>,
+}
+
+fn decode_hex(data: &str) -> Vec<u8> {
+    hex::decode(data.trim_start_matches("0x")).expect("invalid hex")
+}
+
+fn encode_hex(data: &[u8]) -> String {
+    format!("0x{}", hex::encode(data))
+}
+
+fn main() {
+    sp1_sdk::utils::setup_logger();
+    let args = Args::parse();
+
+    let input = std::fs::read_to_string(&args.input).expect("failed to read the input");
+    let requests: Vec<FixtureRequest> = serde_json::from_str(&input).expect("invalid input");
+
+    let mut batch = ProvingBatch::default();
+    for request in requests {
+        batch.push(&decode_hex(&request.bytecode), decode_hex(&request.calldata));
+    }
+
+    // Execute natively to get the outputs and build the same tree as the program.
+    let executed = batch.execute();
+    let mut leafs = vec![];
+    let mut fixtures = vec![];
+    for (request, executed) in batch.requests.iter().zip(executed) {
+        let Some((leaf, outcome)) = executed else {
+            println!("Skipping unprovable request to {}", encode_hex(&request.code_hash));
+            continue;
+        };
+        leafs.push(leaf);
+        fixtures.push((leaf, RequestFixture {
+            code_hash: encode_hex(&request.code_hash),
+            input_hash: encode_hex(&keccak256(request.calldata.as_slice())),
+            status: outcome.status as u8,
+            output_hash: encode_hex(&keccak256(outcome.output.as_slice())),
+            output: encode_hex(&outcome.output),
+            merkle_proof: vec![],
+        }));
+    }
+    let tree = MerkleTree::new(leafs);
+    for (leaf, fixture) in fixtures.iter_mut() {
+        fixture.merkle_proof = tree.proof(*leaf).unwrap().iter().map(|node| encode_hex(node)).collect();
+    }
+
+    let requests = fixtures.into_iter().map(|(_, fixture)| fixture).collect();
+    let fixture = if args.mock {
+        let public_values = PublicValuesStruct { _merkleRoot: tree.root().into() };
+        SP1ProofFixture {
+            merkle_root: encode_hex(&tree.root()),
+            vkey: String::new(),
+            public_values: encode_hex(&PublicValuesStruct::abi_encode(&public_values)),
+            proof: String::new(),
+            requests,
+        }
+    } else {
+        let client = ProverClient::new();
+        let (pk, vk) = client.setup(SOLC_ZKMOD_ELF);
+        let mut stdin = SP1Stdin::new();
+        stdin.write(&batch);
+        let proof = match args.system {
+            ProofSystem::Plonk => client.prove(&pk, stdin).plonk().run(),
+            ProofSystem::Groth16 => client.prove(&pk, stdin).groth16().run(),
+        }.expect("failed to generate proof");
+        client.verify(&proof, &vk).expect("failed to verify proof");
+
+        // Deserialize the public values.
+        let bytes = proof.public_values.as_slice();
+        let PublicValuesStruct { _merkleRoot } = PublicValuesStruct::abi_decode(bytes, false).unwrap();
+        assert_eq!(_merkleRoot.0, tree.root(), "program and native execution differ");
+
+        // Create the testing fixture so we can test things end-to-end.
+        SP1ProofFixture {
+            merkle_root: encode_hex(&_merkleRoot.0),
+            vkey: vk.bytes32().to_string(),
+            public_values: encode_hex(bytes),
+            proof: encode_hex(&proof.bytes()),
+            requests,
+        }
+    };
+
+    // The verification key is used to verify that the proof corresponds to the execution of the
+    // program on the given input.
+    //
+    // Note that the verification key stays the same regardless of the input.
+    println!("Verification Key: {}", fixture.vkey);
+
+    // The public values are the values which are publicly committed to by the zkVM.
+    println!("Public Values: {}", fixture.public_values);
+
+    // The proof proves to the verifier that the program was executed with some inputs that led to
+    // the give public values.
+    println!("Proof Bytes: {}", fixture.proof);
+
+    // Save the fixture to a file.
+    std::fs::create_dir_all(&args.out).expect("failed to create fixture path");
+    let name = if args.mock { "mock".to_string() } else { format!("{:?}", args.system).to_lowercase() };
+    let fixture_path = args.out.join(format!("{}-fixture.json", name));
+    std::fs::write(
+        &fixture_path,
+        serde_json::to_string_pretty(&fixture).unwrap(),
+    )
+        .expect("failed to write fixture");
+    println!("Fixture written to {}", fixture_path.display());
+}
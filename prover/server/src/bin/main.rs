@@ -1,193 +1,152 @@
-use axum::{
-    extract::{Path, Json, State},
-    response::IntoResponse,
-    routing::{get, post},
-    Router,
-};
-use serde::{Deserialize, Serialize};
-use std::{collections::HashSet, env, sync::{Arc, Mutex}};
-use std::collections::HashMap;
-use std::hash::Hash;
-use sha2::{Digest, Sha256};
-use web3::types::{CallRequest, H160};
-use solc_zkmod_lib::{keccak256, prover::prover, run_evm};
-use solc_zkmod_lib::prover::prover::ProvingInput;
-
-// Shared state to keep track of submitted proofs
-struct AppState {
-    submitted_proofs: Mutex<HashSet<String>>,
-    known_bytecodes: HashMap<[u8; 32], Vec<u8>>,
-    known_req_data: HashMap<String, ReqData>,
-    prover: prover::Prover,
-}
-
-struct ReqData {
-    bytecode: Vec<u8>,
-    calldata: Vec<u8>,
-    merkle_proof: Vec<u8>,
-}
-
-#[derive(Deserialize)]
-struct AddBytecodeRequest {
-    bytecode: Vec<u8>,
-}
-
-struct ProofRequestData {
-    pub address: [u8; 20],
-    pub calldata: Vec<u8>,
-    pub value: u64,
-    pub sender: [u8; 20],
-}
-
-#[derive(Deserialize)]
-struct ProofRequest {
-    requests: Vec<ProofRequestData>,
-}
-
-
-#[derive(Serialize)]
-struct ProofResponse {
-    id: String,
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::Parser;
+use tracing_subscriber::EnvFilter;
+use solc_zkmod_lib::prover::backend::BackendConfig;
+use solc_zkmod_lib::prover::prover::{ProofSystem, Prover};
+use solc_zkmod_server::batcher::Batcher;
+use solc_zkmod_server::chain::{Chain, JsonRpcBackend};
+use solc_zkmod_server::config::{ChainConfig, Config};
+use solc_zkmod_server::events::run_webhooks;
+use solc_zkmod_server::routes::router;
+use solc_zkmod_server::state::AppState;
+use solc_zkmod_server::store::Store;
+use solc_zkmod_server::submitter::{Submitter, SubmitterConfig};
+
+/// Flags override the values from the config file, see `solc_zkmod_server::config`.
+#[derive(Parser, Debug)]
+struct Args {
+    /// TOML config file.
+    #[arg(long, env = "CONFIG")]
+    config: Option<PathBuf>,
+    #[arg(long)]
+    bind_address: Option<SocketAddr>,
+    #[arg(long, env = "STORAGE_PATH")]
+    storage_path: Option<PathBuf>,
+    #[arg(long, value_enum)]
+    proof_mode: Option<ProofSystem>,
+    #[arg(long)]
+    workers: Option<usize>,
+    /// Mock proofs of the natively executed batches, for tests.
+    #[arg(long)]
+    mock_proofs: bool,
+    /// `chain_id,rpc_url,oracle`, can be repeated.
+    #[arg(long = "chain")]
+    chains: Vec<ChainConfig>,
+    #[arg(long)]
+    batch_max_calls: Option<usize>,
+    #[arg(long)]
+    batch_max_wait_secs: Option<u64>,
+    /// Enables the admin routes.
+    #[arg(long, env = "ADMIN_TOKEN", hide_env_values = true)]
+    admin_token: Option<String>,
+    /// Enables the submitter with the key, if it's not configured.
+    #[arg(long, env = "SUBMITTER_KEY", hide_env_values = true)]
+    submitter_key: Option<String>,
 }
 
-#[derive(Serialize)]
-struct WitnessOutput {
-    output: Vec<u8>,
-}
-
-// Handler for /request-proof
-async fn request_proof(
-    Json(payload): Json<ProofRequest>,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    let mut req_hash = Sha256::digest([]);
-    let mut proving_inputs = vec![];
-
-    for req in payload.requests {
-        let (code_hash, calldata) = make_eth_call(req);
-        let bytecode = state.known_bytecodes.get(code_hash).unwrap();
-
-        let concatenated = format!("{:?}{:?}", bytecode.clone(), calldata);
-        let hash = format!("{:x}", Sha256::digest(concatenated));
-        req_hash = Sha256::digest(hash.as_bytes().to_vec().append(&mut req_hash.to_vec()));
-        proving_inputs.push(ProvingInput {
-            bytecode: bytecode.clone(),
-            calldata,
-        })
-    }
-
-    let req_id = format!("{:x}", req_hash);
-    let mut state_guard = state.submitted_proofs.lock().unwrap();
-    state_guard.insert(req_id.clone());
-    drop(state_guard);
-
-    state.prover.prove(req_id.clone(), proving_inputs);
-
-    Json(ProofResponse { id: req_id })
-}
-
-async fn make_eth_call(data: ProofRequestData) -> Result<([u8], [u8]), String> {
-    let rpc_url = env::var("RPC_URL")?;
-    let transport = web3::transports::Http::new(rpc_url.as_str());
-    let web3 = web3::Web3::new(transport);
-
-    // Smart contract address (replace with your contract's address)
-    let contract_address: H160 = data.address
-        .parse()
-        .expect("Invalid contract address");
-
-    // Sender address (can be zero address for read-only calls)
-    let sender_address: H160 = data.sender
-        .parse()
-        .expect("Invalid sender address");
-
-    // Create the CallRequest
-    let call_request = CallRequest {
-        from: Some(sender_address),
-        to: Some(contract_address),
-        gas: None,
-        gas_price: None,
-        value: Some(data.value),
-        data: Some(data.calldata),
-        transaction_type: None,
-        access_list: None,
-        max_fee_per_gas: None,
-        max_priority_fee_per_gas: None,
-    };
-
-    // Make the eth_call
-    let result: Vec<u8> = web3.eth().call(call_request, None).await?;
-    if hex::decode("deadbeef")? != result[..32].to_vec() {
-        return Err("missing prefix");
+impl Args {
+    fn config(self) -> Result<Config, String> {
+        let mut config = match &self.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        config.bind_address = self.bind_address.unwrap_or(config.bind_address);
+        config.storage_path = self.storage_path.unwrap_or(config.storage_path);
+        config.proof_mode = self.proof_mode.unwrap_or(config.proof_mode);
+        config.workers = self.workers.unwrap_or(config.workers);
+        if self.mock_proofs {
+            config.prover = BackendConfig::Mock;
+        }
+        for chain in self.chains {
+            config.set_chain(chain);
+        }
+        config.batch.max_calls = self.batch_max_calls.unwrap_or(config.batch.max_calls);
+        config.batch.max_wait_secs = self.batch_max_wait_secs.unwrap_or(config.batch.max_wait_secs);
+        config.admin_token = self.admin_token.or(config.admin_token);
+        if let Some(private_key) = self.submitter_key {
+            config.submitter.get_or_insert_with(SubmitterConfig::default).private_key = private_key;
+        }
+        config.validate()?;
+        Ok(config)
     }
-
-    let code_hash = result[32..64];
-    let input_data = result[64..];
-
-    Ok((code_hash, input_data))
-}
-
-async fn get_witness(
-    Path(id): Path<String>,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    let proof = state.prover.get_proof(id.clone()).unwrap();
-    let witness_data = state.known_req_data.get(id.clone().as_str()).unwrap().clone();
-    let result = run_evm(witness_data.bytecode.clone(), witness_data.calldata.clone()).unwrap();
-
-    let output = [
-        *[result.len() as u8],
-        result[..],
-        *[witness_data.merkle_proof.len() as u8],
-        witness_data.merkle_proof[..],
-    ].concat();
-
-    Json(WitnessOutput { output })
-}
-
-async fn add_bytecode(
-    Json(payload): Json<AddBytecodeRequest>,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    state.known_bytecodes.insert(keccak256(payload.bytecode.clone().as_slice()), payload.bytecode);
-}
-
-// Handler for /check-proof/{id}
-async fn check_proof(
-    Path(id): Path<String>,
-    State(state): State<Arc<AppState>>,
-) -> impl IntoResponse {
-    let proof = state.prover.get_proof(id);
-
-    Json(serde_json::json!({ "exists": proof.is_some() }))
 }
 
 #[tokio::main]
 async fn main() {
-    let prover = prover::Prover::new();
-
-    // Shared state to store proof IDs
-    let state = Arc::new(AppState {
-        submitted_proofs: Mutex::new(HashSet::new()),
-        known_bytecodes: HashMap::new(),
-        known_req_data: HashMap::new(),
-        prover,
+    // JSON lines with the fields of the current span and its parents, filtered by `RUST_LOG`.
+    tracing_subscriber::fmt()
+        .json()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")))
+        .with_current_span(true)
+        .with_span_list(true)
+        .init();
+
+    let config = Args::parse().config().expect("Invalid config");
+
+    let backend = config.prover.backend().expect("Invalid prover backend");
+    let prover = Arc::new(Prover::new(config.proof_mode, backend));
+    let chains = config.chains.iter().map(|chain| {
+        let backend = JsonRpcBackend::new(chain.chain_id, &chain.rpc_url).expect("Invalid RPC URL");
+        (chain.chain_id, Chain { backend: Box::new(backend), oracle: chain.oracle })
+    }).collect();
+    let store = Arc::new(Store::open(config.storage_path.clone()).expect("Failed to open the store"));
+    let submitter = config.submitter.clone().map(|submitter| {
+        let submitter = Submitter::new(submitter, &config.chains).expect("Invalid submitter config");
+        tracing::info!(address = ?submitter.address(), "Submitting the roots");
+        Arc::new(submitter)
     });
+    let batcher = Arc::new(Batcher::new(config.batch.clone(), config.workers, prover.clone(), submitter, store.clone()));
 
+    // Shared state to store proof IDs
+    let state = Arc::new(AppState::new(chains, prover, batcher.clone(), store));
+    let jobs = state.recover_jobs().expect("Failed to recover the jobs");
+    batcher.recover().expect("Failed to recover the batches");
+    tracing::info!(jobs, "Recovered the jobs");
+    tokio::spawn(batcher.clone().run());
+    tokio::spawn(run_webhooks(state.clone(), state.batcher.subscribe()));
+    tokio::spawn(shutdown_signal(batcher.clone()));
 
     // Build the app with routes
-    let app = Router::new()
-        .route("/request-proof", post(request_proof))
-        .route("/check-proof/:id", get(check_proof))
-        .route("/add-bytecode", post(add_bytecode))
-        .route("/get-witness/:id", get(get_witness))
-        .with_state(state.clone());
-
+    let app = router(state.clone(), config.admin_token.clone());
 
     // Start the server
-    println!("Running the server at 127.0.0.1:3000");
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000").await.unwrap();
+    tracing::info!(address = %config.bind_address, "Running the server");
+    let listener = tokio::net::TcpListener::bind(config.bind_address).await.unwrap();
+    let shutting_down = batcher.clone();
     axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(async move { shutting_down.shutting_down().await })
         .await
         .unwrap();
+
+    let timeout = Duration::from_secs(config.shutdown_timeout_secs);
+    tracing::info!(timeout_secs = config.shutdown_timeout_secs, "Waiting for the proofs and submissions in progress");
+    if !batcher.drain(timeout).await {
+        // Dropping the runtime would wait for the blocking proving threads.
+        tracing::warn!("Shutdown timed out, the unfinished batches are resumed on the next start");
+        std::process::exit(0);
+    }
+    tracing::info!("Server stopped");
+}
+
+/// Stops accepting requests and new proofs on Ctrl+C or SIGTERM.
+async fn shutdown_signal(batcher: Arc<Batcher>) {
+    let ctrl_c = tokio::signal::ctrl_c();
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+    tracing::info!("Shutting down");
+    batcher.shutdown();
 }
@@ -0,0 +1,11 @@
+//! Prints the OpenAPI document of the server, also served at `/openapi.json`:
+//! ```shell
+//! cargo run --release --bin openapi > openapi.json
+//! ```
+
+use utoipa::OpenApi;
+use solc_zkmod_server::routes::ApiDoc;
+
+fn main() {
+    println!("{}", ApiDoc::openapi().to_pretty_json().expect("Failed to serialize the document"));
+}
@@ -0,0 +1,129 @@
+//! Builds the prover program reproducibly and prints its verification key.
+//!
+//! You can run this script using the following command:
+//! ```shell
+//! RUST_LOG=info cargo run --release --bin vkey -- --build --check
+//! ```
+//! Use `--write` to record the new key after the program was changed.
+
+use std::path::PathBuf;
+use std::process::Command;
+use alloy_sol_types::SolType;
+use clap::Parser;
+use sp1_sdk::{HashableKey, ProverClient, SP1Stdin};
+use solc_zkmod_lib::batch::ProvingBatch;
+use solc_zkmod_lib::merkle::MerkleTree;
+use solc_zkmod_lib::PublicValuesStruct;
+
+/// Docker image tag matching the `sp1-sdk` version, the same tag always produces the same ELF.
+const SP1_DOCKER_TAG: &str = "v3.0.0";
+const ELF_NAME: &str = "riscv32im-succinct-zkvm-elf";
+/// Returns its calldata: `CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN`.
+const ECHO_CODE: [u8; 7] = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+
+#[derive(Parser, Debug)]
+struct Args {
+    /// Rebuild the program in the SP1 docker image before computing the key.
+    #[arg(long)]
+    build: bool,
+    /// Exit with an error if the key differs from the recorded one or from `ComputationOracle.program_key`,
+    /// or if the program commits another root than the native execution of the host.
+    #[arg(long)]
+    check: bool,
+    /// Record the computed key.
+    #[arg(long)]
+    write: bool,
+}
+
+fn prover_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("..")
+}
+
+fn build_program() {
+    let status = Command::new("cargo")
+        .current_dir(prover_dir().join("program"))
+        .args(["prove", "build", "--docker", "--locked"])
+        .args(["--tag", SP1_DOCKER_TAG])
+        .args(["--elf-name", ELF_NAME])
+        .args(["--output-directory", "../elf"])
+        .status()
+        .expect("failed to run `cargo prove`");
+    assert!(status.success(), "reproducible build of the program failed");
+}
+
+/// Reads `program_key` initial value from the oracle source.
+fn oracle_program_key() -> Option<String> {
+    let path = prover_dir().join("../contracts/src/ComputationOracle.sol");
+    let source = std::fs::read_to_string(path).ok()?;
+    let line = source.lines().find(|line| line.contains("bytes32 public program_key"))?;
+    let value = line.split('=').nth(1)?.trim().trim_end_matches(';');
+    Some(value.to_lowercase())
+}
+
+/// Executes a sample batch with the program, an ELF built before a change of the input or the leaves
+/// fails or commits another root.
+fn check_execution(elf: &[u8]) -> Result<(), String> {
+    let mut batch = ProvingBatch::default();
+    batch.push(&ECHO_CODE, b"echo".to_vec());
+    batch.push(&ECHO_CODE, vec![]);
+    let leaves = batch.execute().into_iter().flatten().map(|(leaf, _)| leaf).collect::<Vec<_>>();
+
+    let mut stdin = SP1Stdin::new();
+    stdin.write(&batch);
+    let (public_values, _) = ProverClient::new()
+        .execute(elf, stdin)
+        .run()
+        .map_err(|err| format!("Program execution failed: {}", err))?;
+    let PublicValuesStruct { _merkleRoot } = PublicValuesStruct::abi_decode(public_values.as_slice(), false)
+        .map_err(|err| format!("Invalid public values: {}", err))?;
+    if _merkleRoot.0 != MerkleTree::new(leaves).root() {
+        return Err("Program commits another root than the native execution".to_string());
+    }
+    Ok(())
+}
+
+fn main() {
+    sp1_sdk::utils::setup_logger();
+    let args = Args::parse();
+
+    if args.build {
+        build_program();
+    }
+
+    let elf_dir = prover_dir().join("elf");
+    let elf = std::fs::read(elf_dir.join(ELF_NAME)).expect("failed to read the program ELF");
+    let (_, vk) = ProverClient::new().setup(&elf);
+    let vkey = vk.bytes32().to_lowercase();
+
+    println!("Program Verification Key: {}", vkey);
+
+    let vkey_path = elf_dir.join("vkey");
+    if args.write {
+        std::fs::write(&vkey_path, format!("{}\n", vkey)).expect("failed to write the key");
+        println!("Recorded in {}", vkey_path.display());
+    }
+
+    if args.check {
+        let recorded = std::fs::read_to_string(&vkey_path).expect("failed to read the recorded key");
+        let mut matches = true;
+        if recorded.trim().to_lowercase() != vkey {
+            println!("Recorded key differs: {}", recorded.trim());
+            matches = false;
+        }
+        match oracle_program_key() {
+            Some(key) if key == vkey => {}
+            key => {
+                println!("ComputationOracle.program_key differs: {}", key.unwrap_or_default());
+                matches = false;
+            }
+        }
+        if let Err(err) = check_execution(&elf) {
+            println!("{}, rebuild it with --build --write", err);
+            matches = false;
+        }
+        if !matches {
+            std::process::exit(1);
+        }
+        println!("Recorded key and ComputationOracle.program_key match the program");
+    }
+}
@@ -0,0 +1,235 @@
+//! Simulation of the transactions to discover the `@free` calls before proving them.
+
+use std::sync::Mutex;
+use async_trait::async_trait;
+use serde_json::Value;
+use solc_zkmod_calldata::discovery::{decode_discovery, Discovery};
+use revm::db::{AccountState, CacheDB, EmptyDB};
+use revm::primitives::{AccountInfo, Address, Bytecode, ExecutionResult, Output, TxKind};
+use revm::Evm;
+use web3::transports::Http;
+use web3::types::{Bytes, CallRequest, H160};
+use web3::{Transport, Web3};
+use crate::metrics::rpc_error;
+
+/// Outcome of a call simulated on top of the latest state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallResult {
+    Success(Vec<u8>),
+    Revert(Vec<u8>),
+}
+
+/// Deployment of the oracle on a chain.
+pub struct Chain {
+    pub backend: Box<dyn ChainBackend>,
+    /// `ComputationOracle` called by the generated code, stubbed in the simulations.
+    pub oracle: H160,
+}
+
+/// Code of the stubbed accounts, `STOP`, so every call to them succeeds.
+const STUB_CODE: [u8; 1] = [0x00];
+
+#[async_trait]
+pub trait ChainBackend: Send + Sync {
+    /// Simulates the call with the code of the `stubbed` accounts replaced with `STUB_CODE`.
+    async fn call(&self, request: CallRequest, stubbed: &[H160]) -> Result<CallResult, String>;
+}
+
+/// Simulates calls with `eth_call` of a JSON-RPC node.
+pub struct JsonRpcBackend {
+    chain_id: u64,
+    web3: Web3<Http>,
+}
+
+impl JsonRpcBackend {
+    pub fn new(chain_id: u64, rpc_url: &str) -> Result<Self, String> {
+        let transport = Http::new(rpc_url).map_err(|err| err.to_string())?;
+        Ok(Self { chain_id, web3: Web3::new(transport) })
+    }
+}
+
+#[async_trait]
+impl ChainBackend for JsonRpcBackend {
+    async fn call(&self, request: CallRequest, stubbed: &[H160]) -> Result<CallResult, String> {
+        let mut params = vec![serde_json::to_value(&request).map_err(|err| err.to_string())?, Value::from("latest")];
+        // State override set, supported by the most of the nodes.
+        if !stubbed.is_empty() {
+            params.push(Value::Object(stubbed.iter().map(|address| (
+                format!("{:?}", address),
+                serde_json::json!({ "code": Bytes(STUB_CODE.to_vec()) }),
+            )).collect()));
+        }
+        match self.web3.transport().execute("eth_call", params).await {
+            Ok(result) => serde_json::from_value::<Bytes>(result)
+                .map(|result| CallResult::Success(result.0))
+                .map_err(|err| err.to_string()),
+            // Revert data is returned in the error data, as a hex string or nested in an object.
+            Err(web3::Error::Rpc(err)) => {
+                let data = match &err.data {
+                    Some(Value::String(data)) => Some(data.as_str()),
+                    Some(Value::Object(object)) => object.get("data").and_then(Value::as_str),
+                    _ => None,
+                };
+                match data {
+                    Some(data) => hex::decode(data.trim_start_matches("0x"))
+                        .map(CallResult::Revert)
+                        .map_err(|err| err.to_string()),
+                    None => Err(rpc_error(self.chain_id, "simulation", err)),
+                }
+            }
+            Err(err) => Err(rpc_error(self.chain_id, "simulation", err)),
+        }
+    }
+}
+
+/// Simulates calls with an embedded EVM, only the accounts loaded with `set_code` have code.
+#[derive(Default)]
+pub struct LocalBackend {
+    db: Mutex<CacheDB<EmptyDB>>,
+}
+
+impl LocalBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the deployed (runtime) bytecode of the account.
+    pub fn set_code(&self, address: H160, bytecode: Vec<u8>) {
+        insert_code(&mut self.db.lock().unwrap(), address, bytecode);
+    }
+}
+
+fn insert_code(db: &mut CacheDB<EmptyDB>, address: H160, bytecode: Vec<u8>) {
+    let bytecode = Bytecode::new_raw(bytecode.into());
+    db.insert_account_info(
+        Address::from_slice(address.as_bytes()),
+        AccountInfo {
+            code_hash: bytecode.hash_slow(),
+            code: Some(bytecode),
+            ..Default::default()
+        },
+    );
+}
+
+#[async_trait]
+impl ChainBackend for LocalBackend {
+    async fn call(&self, request: CallRequest, stubbed: &[H160]) -> Result<CallResult, String> {
+        let mut db = self.db.lock().unwrap().clone();
+        for address in stubbed {
+            insert_code(&mut db, *address, STUB_CODE.to_vec());
+        }
+        let caller = Address::from_slice(request.from.unwrap_or_default().as_bytes());
+        let value = {
+            let mut bytes = [0u8; 32];
+            request.value.unwrap_or_default().to_big_endian(&mut bytes);
+            revm::primitives::U256::from_be_bytes(bytes)
+        };
+        // The caller is funded, as `eth_call` doesn't check the balance either.
+        let caller_account = db.load_account(caller).map_err(|err| format!("{:?}", err))?;
+        caller_account.info.balance = value;
+        if caller_account.account_state == AccountState::NotExisting {
+            caller_account.account_state = AccountState::None;
+        }
+
+        let mut evm = Evm::builder()
+            .with_db(db)
+            .modify_tx_env(|tx| {
+                tx.caller = caller;
+                tx.transact_to = TxKind::Call(Address::from_slice(request.to.unwrap_or_default().as_bytes()));
+                tx.data = request.data.map(|data| data.0).unwrap_or_default().into();
+                tx.value = value;
+            })
+            .build();
+
+        match evm.transact().map_err(|err| format!("{:?}", err))?.result {
+            ExecutionResult::Success { output: Output::Call(output), .. } => Ok(CallResult::Success(output.to_vec())),
+            ExecutionResult::Success { .. } => Ok(CallResult::Success(vec![])),
+            ExecutionResult::Revert { output, .. } => Ok(CallResult::Revert(output.to_vec())),
+            ExecutionResult::Halt { reason, .. } => Err(format!("Call halted: {:?}", reason)),
+        }
+    }
+}
+
+///
+/// Simulates the transaction with the oracle stubbed, so the calls with appended witnesses succeed
+/// before they are proven. Returns the next `@free` call from its discovery revert, `None` if the
+/// transaction succeeds.
+///
+pub async fn make_eth_call(backend: &dyn ChainBackend, request: CallRequest, oracle: H160) -> Result<Option<Discovery>, String> {
+    match backend.call(request, &[oracle]).await? {
+        CallResult::Revert(data) => decode_discovery(&data)
+            .map(Some)
+            .ok_or(format!("Reverted without discovery: 0x{}", hex::encode(data))),
+        CallResult::Success(_) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::U256;
+
+    const CONTRACT: H160 = H160([1; 20]);
+    const ORACLE: H160 = H160([2; 20]);
+
+    fn request(data: Vec<u8>) -> CallRequest {
+        CallRequest {
+            from: Some(H160([3; 20])),
+            to: Some(CONTRACT),
+            data: Some(Bytes(data)),
+            ..Default::default()
+        }
+    }
+
+    /// Reverts with `data`, copied from the end of the code.
+    fn revert_code(data: &[u8]) -> Vec<u8> {
+        let len = (data.len() as u16).to_be_bytes();
+        [&[0x61, len[0], len[1], 0x60, 12, 0x5f, 0x39, 0x61, len[0], len[1], 0x5f, 0xfd][..], data].concat()
+    }
+
+    #[tokio::test]
+    async fn local_call() {
+        let backend = LocalBackend::new();
+        // Returns the calldata.
+        backend.set_code(CONTRACT, vec![0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3]);
+        let result = backend.call(request(vec![1, 2, 3]), &[]).await.unwrap();
+        assert_eq!(result, CallResult::Success(vec![1, 2, 3]));
+
+        // The caller is funded with the value.
+        let request = CallRequest { value: Some(U256::from(1000)), ..request(vec![]) };
+        assert_eq!(backend.call(request, &[]).await.unwrap(), CallResult::Success(vec![]));
+
+        backend.set_code(CONTRACT, revert_code(&[4, 5]));
+        assert_eq!(backend.call(self::request(vec![]), &[]).await.unwrap(), CallResult::Revert(vec![4, 5]));
+    }
+
+    #[tokio::test]
+    async fn local_stubbed_accounts() {
+        let backend = LocalBackend::new();
+        // Returns the code size of the oracle.
+        let code = [&[0x73][..], ORACLE.as_bytes(), &[0x3b, 0x5f, 0x52, 0x60, 0x20, 0x5f, 0xf3]].concat();
+        backend.set_code(CONTRACT, code);
+        let size = |result| match result {
+            CallResult::Success(output) => U256::from_big_endian(&output),
+            result => panic!("Unexpected {:?}", result),
+        };
+        assert_eq!(size(backend.call(request(vec![]), &[]).await.unwrap()), U256::zero());
+        assert_eq!(size(backend.call(request(vec![]), &[ORACLE]).await.unwrap()), U256::one());
+        // The stub is only in the simulation.
+        assert_eq!(size(backend.call(request(vec![]), &[]).await.unwrap()), U256::zero());
+    }
+
+    #[tokio::test]
+    async fn discovery() {
+        let backend = LocalBackend::new();
+        let discovery = Discovery { code_hash: [7; 32], input: vec![1, 2, 3] };
+        backend.set_code(CONTRACT, revert_code(&solc_zkmod_calldata::discovery::encode_discovery(&discovery)));
+        assert_eq!(make_eth_call(&backend, request(vec![]), ORACLE).await.unwrap(), Some(discovery));
+
+        backend.set_code(CONTRACT, revert_code(&[4, 5]));
+        assert!(make_eth_call(&backend, request(vec![]), ORACLE).await.is_err());
+
+        backend.set_code(CONTRACT, vec![0x00]);
+        assert_eq!(make_eth_call(&backend, request(vec![]), ORACLE).await.unwrap(), None);
+    }
+}
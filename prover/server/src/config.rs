@@ -0,0 +1,150 @@
+//! Server configuration, read from a TOML file and overridden by the command line flags.
+//!
+//! ```toml
+//! bind_address = "127.0.0.1:3000"
+//! storage_path = "storage"
+//! proof_mode = "groth16"
+//! workers = 1
+//! shutdown_timeout_secs = 60
+//! # Optional, enables the /admin routes
+//! admin_token = "..."
+//!
+//! # Optional, "local" by default, or "mock"
+//! [prover]
+//! backend = "network"
+//! private_key = "0x..."
+//! rpc_url = "https://rpc.succinct.xyz"
+//!
+//! [[chains]]
+//! chain_id = 11155111
+//! rpc_url = "https://ethereum-sepolia-rpc.publicnode.com"
+//! oracle = "0x71FC499614deD4872903239924049f5789e636ed"
+//!
+//! [[chains]]
+//! chain_id = 84532
+//! rpc_url = "https://sepolia.base.org"
+//! oracle = "0x71FC499614deD4872903239924049f5789e636ed"
+//!
+//! [batch]
+//! max_calls = 16
+//! max_wait_secs = 60
+//! per_chain = true
+//!
+//! # Optional, the roots are posted by hand without it
+//! [submitter]
+//! private_key = "0x..."
+//! dummy_verifier = false
+//! max_retries = 3
+//! receipt_timeout_secs = 120
+//! ```
+
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use serde::Deserialize;
+use web3::types::H160;
+use solc_zkmod_lib::prover::backend::BackendConfig;
+use solc_zkmod_lib::prover::prover::ProofSystem;
+use crate::batcher::BatchPolicy;
+use crate::submitter::SubmitterConfig;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind_address: SocketAddr,
+    pub storage_path: PathBuf,
+    pub proof_mode: ProofSystem,
+    pub prover: BackendConfig,
+    /// Batches proven at the same time.
+    pub workers: usize,
+    /// Time to finish the proofs and submissions in progress on shutdown, the rest is resumed on
+    /// the next start.
+    pub shutdown_timeout_secs: u64,
+    /// Required by the admin routes in the `x-admin-token` header, they are disabled without it.
+    pub admin_token: Option<String>,
+    pub chains: Vec<ChainConfig>,
+    pub batch: BatchPolicy,
+    /// Posts the proven roots to the oracles if set.
+    pub submitter: Option<SubmitterConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainConfig {
+    pub chain_id: u64,
+    /// JSON-RPC endpoint used to simulate the transactions.
+    pub rpc_url: String,
+    /// `ComputationOracle` called by the generated code.
+    pub oracle: H160,
+}
+
+/// Parses `chain_id,rpc_url,oracle`, the format of the `--chain` flag.
+impl FromStr for ChainConfig {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let [chain_id, rpc_url, oracle] = value.split(',').collect::<Vec<_>>()[..] else {
+            return Err("Expected chain_id,rpc_url,oracle".to_string());
+        };
+        Ok(Self {
+            chain_id: chain_id.parse().map_err(|err| format!("Invalid chain id: {}", err))?,
+            rpc_url: rpc_url.to_string(),
+            oracle: oracle.parse().map_err(|err| format!("Invalid oracle: {}", err))?,
+        })
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind_address: ([127, 0, 0, 1], 3000).into(),
+            storage_path: "storage".into(),
+            proof_mode: ProofSystem::default(),
+            prover: BackendConfig::default(),
+            workers: 1,
+            shutdown_timeout_secs: 60,
+            admin_token: None,
+            chains: vec![],
+            batch: BatchPolicy::default(),
+            submitter: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let config = std::fs::read_to_string(path)
+            .map_err(|err| format!("Failed to read {}: {}", path.display(), err))?;
+        toml::from_str(&config).map_err(|err| format!("Invalid {}: {}", path.display(), err))
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.workers == 0 {
+            return Err("At least one worker is required".to_string());
+        }
+        if self.batch.max_calls == 0 {
+            return Err("Batch max_calls must be positive".to_string());
+        }
+        if self.chains.is_empty() {
+            return Err("No chains are configured".to_string());
+        }
+        // Without the dummy verifier the oracle rejects the mock proofs.
+        if self.prover == BackendConfig::Mock && self.submitter.as_ref().is_some_and(|submitter| !submitter.dummy_verifier) {
+            return Err("Mock proofs require dummy_verifier = true in the submitter config".to_string());
+        }
+        let mut chain_ids = HashSet::new();
+        if let Some(chain) = self.chains.iter().find(|chain| !chain_ids.insert(chain.chain_id)) {
+            return Err(format!("Chain {} is configured twice", chain.chain_id));
+        }
+        Ok(())
+    }
+
+    /// Adds the chain, or replaces the one with the same id.
+    pub fn set_chain(&mut self, chain: ChainConfig) {
+        match self.chains.iter_mut().find(|configured| configured.chain_id == chain.chain_id) {
+            Some(configured) => *configured = chain,
+            None => self.chains.push(chain),
+        }
+    }
+}
@@ -0,0 +1,287 @@
+//! Notifies the clients about the status transitions of their jobs, with server-sent events and
+//! signed webhooks.
+
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures::Stream;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::sync::{broadcast, mpsc};
+use tracing::Instrument;
+use solc_zkmod_api::{JobEvent, JobStatus, Submission, Webhook};
+use crate::routes::job_witnesses;
+use crate::state::AppState;
+
+/// Unix time of the delivery, in seconds.
+pub const TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+/// Hex-encoded HMAC-SHA256 of `{timestamp}.{body}` with the webhook secret.
+pub const SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+const WEBHOOK_RETRIES: u32 = 3;
+
+/// Current state of the job, `None` if the id is unknown.
+fn job_event(state: &AppState, id: &str) -> Option<JobEvent> {
+    let job = state.submitted_proofs.lock().unwrap().get(id).cloned()?;
+    let (status, submission, mut error, proven) = match job.batch_id {
+        Some(batch_id) => state.batcher.with_batch(batch_id, |batch| (
+            job.status(Some(batch)),
+            job.submission(Some(batch)),
+            batch.error.clone(),
+            batch.executed.clone(),
+        ))?,
+        None => (job.status(None), job.submission(None), None, None),
+    };
+    if let Some(Submission::Failed { error: submission_error }) = &submission {
+        error = Some(submission_error.clone());
+    }
+
+    let witnesses = match status {
+        JobStatus::Proven | JobStatus::Submitted => {
+            match job_witnesses(&job, proven.as_deref()) {
+                Ok(witnesses) => Some(witnesses),
+                Err((_, err)) => {
+                    error = Some(err);
+                    None
+                }
+            }
+        }
+        _ => None,
+    };
+    Some(JobEvent { id: id.to_string(), status, submission, error, witnesses })
+}
+
+/// Waits for the next change of the batch, `false` if no more changes are coming.
+async fn batch_changed(events: &mut broadcast::Receiver<u64>, batch_id: Option<u64>) -> bool {
+    loop {
+        match events.recv().await {
+            Ok(changed) if Some(changed) == batch_id => return true,
+            Ok(_) => continue,
+            // The missed events may include the batch.
+            Err(broadcast::error::RecvError::Lagged(_)) => return true,
+            Err(broadcast::error::RecvError::Closed) => return false,
+        }
+    }
+}
+
+// Handler for /jobs/{id}/events, streams the job state on every status change and closes the
+// stream once the status is final
+#[utoipa::path(
+    get,
+    path = "/jobs/{id}/events",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "`status` events with the job state", content_type = "text/event-stream", body = JobEvent),
+        (status = 404, description = "Unknown id", body = String),
+    ),
+    security(("api_key" = [])),
+)]
+#[tracing::instrument(skip_all, fields(job_id = %id))]
+pub async fn job_events(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, (StatusCode, String)> {
+    let batch_id = state.submitted_proofs.lock().unwrap()
+        .get(&id)
+        .map(|job| job.batch_id)
+        .ok_or((StatusCode::NOT_FOUND, "Unknown id".to_string()))?;
+    // Subscribed before the first state is read, so no transition is missed.
+    let mut events = state.batcher.subscribe();
+
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut last_status = None;
+        loop {
+            let Some(event) = job_event(&state, &id) else {
+                return;
+            };
+            if last_status != Some(event.status) {
+                last_status = Some(event.status);
+                let is_final = event.is_final();
+                let event = Event::default().event("status").json_data(&event).unwrap();
+                if sender.send(event).await.is_err() || is_final {
+                    tracing::debug!("Event stream closed");
+                    return;
+                }
+            }
+            tokio::select! {
+                _ = sender.closed() => return,
+                // The stream is closed, so the graceful shutdown doesn't wait for it.
+                _ = state.batcher.shutting_down() => return,
+                changed = batch_changed(&mut events, batch_id) => if !changed {
+                    return;
+                },
+            }
+        }
+    }.in_current_span());
+
+    let stream = futures::stream::unfold(receiver, |mut receiver| async move {
+        receiver.recv().await.map(|event| (Ok(event), receiver))
+    });
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+///
+/// Delivers the status transitions of the jobs with a webhook, for the batch changes received
+/// from `events`.
+/// Every status is delivered once, transitions missed by a lagging receiver are collapsed into the
+/// current status.
+///
+pub async fn run_webhooks(state: Arc<AppState>, mut events: broadcast::Receiver<u64>) {
+    let client = reqwest::Client::new();
+    loop {
+        let changed = match events.recv().await {
+            Ok(batch_id) => Some(batch_id),
+            // Some batches changed without an event, so all the jobs are checked.
+            Err(broadcast::error::RecvError::Lagged(_)) => None,
+            Err(broadcast::error::RecvError::Closed) => return,
+        };
+        // Only the jobs whose status moved since the last delivery, their events are built after
+        // the lock is released.
+        let jobs = state.submitted_proofs.lock().unwrap()
+            .iter()
+            .filter(|(_, job)| job.webhook.is_some() && (changed.is_none() || changed == job.batch_id))
+            .filter(|(_, job)| {
+                let Some(batch_id) = job.batch_id else {
+                    return false;
+                };
+                state.batcher.with_batch(batch_id, |batch| job.status(Some(batch))) != job.delivered
+            })
+            .map(|(id, job)| (id.clone(), job.webhook.clone().unwrap()))
+            .collect::<Vec<_>>();
+
+        for (id, webhook) in jobs {
+            let Some(event) = job_event(&state, &id) else {
+                continue;
+            };
+            if state.mark_delivered(&id, event.status) {
+                spawn_delivery(client.clone(), webhook, event);
+            }
+        }
+    }
+}
+
+/// Delivers the current state of the job to its webhook once.
+pub fn notify_webhook(state: Arc<AppState>, id: String) {
+    let webhook = state.submitted_proofs.lock().unwrap().get(&id).and_then(|job| job.webhook.clone());
+    let Some(webhook) = webhook else {
+        return;
+    };
+    tokio::spawn(async move {
+        match job_event(&state, &id) {
+            Some(event) if state.mark_delivered(&id, event.status) => {
+                spawn_delivery(reqwest::Client::new(), webhook, event);
+            }
+            _ => {}
+        }
+    });
+}
+
+fn spawn_delivery(client: reqwest::Client, webhook: Webhook, event: JobEvent) {
+    let span = tracing::info_span!("webhook", job_id = %event.id);
+    tokio::spawn(async move {
+        match deliver(&client, &webhook, &event).await {
+            Ok(()) => tracing::debug!(status = ?event.status, "Webhook delivered"),
+            Err(err) => tracing::warn!(status = ?event.status, error = %err, "Webhook delivery failed"),
+        }
+    }.instrument(span));
+}
+
+async fn deliver(client: &reqwest::Client, webhook: &Webhook, event: &JobEvent) -> Result<(), String> {
+    let body = serde_json::to_string(event).map_err(|err| err.to_string())?;
+    let mut error = String::new();
+    for attempt in 0..=WEBHOOK_RETRIES {
+        if attempt > 0 {
+            tokio::time::sleep(Duration::from_secs(1 << attempt)).await;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs().to_string();
+        let response = client.post(&webhook.url)
+            .header("content-type", "application/json")
+            .header(TIMESTAMP_HEADER, &timestamp)
+            .header(SIGNATURE_HEADER, sign(&webhook.secret, &timestamp, &body))
+            .body(body.clone())
+            .timeout(Duration::from_secs(10))
+            .send()
+            .await;
+        match response {
+            Ok(response) if response.status().is_success() => return Ok(()),
+            Ok(response) => error = format!("Status {}", response.status()),
+            Err(err) => error = err.to_string(),
+        }
+    }
+    Err(error)
+}
+
+fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).unwrap();
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use axum::{routing::post, Json, Router};
+    use solc_zkmod_lib::batch::ProvingBatch;
+    use solc_zkmod_lib::prover::backend::MockBackend;
+    use solc_zkmod_lib::prover::prover::{ProofSystem, Prover};
+    use crate::batcher::{BatchPolicy, Batcher};
+    use crate::state::Job;
+    use crate::store::Store;
+
+    /// Status of the next delivery, `None` if there's none within the timeout.
+    async fn next_delivery(deliveries: &mut mpsc::UnboundedReceiver<JobStatus>) -> Option<JobStatus> {
+        tokio::time::timeout(Duration::from_millis(500), deliveries.recv()).await.ok().flatten()
+    }
+
+    #[tokio::test]
+    async fn lagged_events() {
+        let (delivered, mut deliveries) = mpsc::unbounded_channel();
+        let receiver = Router::new().route("/", post(move |Json(event): Json<JobEvent>| async move {
+            delivered.send(event.status).unwrap();
+        }));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, receiver).await.unwrap() });
+
+        let prover = Arc::new(Prover::new(ProofSystem::Groth16, Box::new(MockBackend::new())));
+        let store = Arc::new(Store::in_memory());
+        let batcher = Arc::new(Batcher::new(BatchPolicy::default(), 1, prover.clone(), None, store.clone()));
+        let state = Arc::new(AppState::new(HashMap::new(), prover, batcher.clone(), store));
+        let mut requests = ProvingBatch::default();
+        requests.push(&[0x00], vec![]);
+        let (batch_id, _) = batcher.add(1, "job", &requests);
+        state.add_job("job".to_string(), Job {
+            chain_id: 1,
+            batch_id: Some(batch_id),
+            transactions: vec![],
+            webhook: Some(Webhook { url, secret: "secret".to_string() }),
+            delivered: None,
+        });
+
+        // A single event is buffered, the receiver lags behind the second one.
+        let (events, receiver) = broadcast::channel(1);
+        tokio::spawn(run_webhooks(state.clone(), receiver));
+        events.send(batch_id).unwrap();
+        assert_eq!(next_delivery(&mut deliveries).await, Some(JobStatus::Pending));
+
+        batcher.cancel(batch_id).unwrap();
+        events.send(batch_id).unwrap();
+        events.send(batch_id).unwrap();
+        assert_eq!(next_delivery(&mut deliveries).await, Some(JobStatus::Failed));
+
+        // The final status isn't delivered again on the next lag.
+        events.send(batch_id).unwrap();
+        events.send(batch_id).unwrap();
+        assert_eq!(next_delivery(&mut deliveries).await, None);
+        assert_eq!(state.submitted_proofs.lock().unwrap()["job"].delivered, Some(JobStatus::Failed));
+    }
+}
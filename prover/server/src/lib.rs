@@ -0,0 +1,11 @@
+pub mod admin;
+pub mod auth;
+pub mod batcher;
+pub mod chain;
+pub mod config;
+pub mod events;
+pub mod metrics;
+pub mod routes;
+pub mod state;
+pub mod store;
+pub mod submitter;
@@ -0,0 +1,160 @@
+//! Prometheus metrics of the server, rendered in the text exposition format at `/metrics`.
+//!
+//! Counters and histograms are recorded where the events happen, the job and batch gauges are
+//! computed from the state on every scrape.
+
+use std::sync::{Arc, LazyLock};
+use axum::{extract::State, http::{header, StatusCode}, response::IntoResponse};
+use prometheus::{
+    histogram_opts, opts, CounterVec, Encoder, Histogram, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec,
+    Registry, TextEncoder,
+};
+use serde::Serialize;
+use solc_zkmod_api::JobStatus;
+use crate::batcher::BatchStatus;
+use crate::state::AppState;
+
+pub static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+pub struct Metrics {
+    registry: Registry,
+    pub proving_duration: HistogramVec,
+    pub execution_duration: Histogram,
+    pub request_cycles: Histogram,
+    pub batch_calls: Histogram,
+    pub submissions: IntCounterVec,
+    pub submission_gas: CounterVec,
+    pub submission_fees: CounterVec,
+    pub rpc_errors: IntCounterVec,
+    queue_depth: IntGauge,
+    batches: IntGaugeVec,
+    jobs: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        let metrics = Self {
+            proving_duration: HistogramVec::new(
+                histogram_opts!(
+                    "prover_proving_duration_seconds",
+                    "Time to prove a batch.",
+                    vec![10.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0, 1800.0, 3600.0]
+                ),
+                &["result"],
+            ).unwrap(),
+            execution_duration: Histogram::with_opts(histogram_opts!(
+                "prover_execution_duration_seconds",
+                "Time to execute the calls of a proof request in the zkVM to count its cycles.",
+                vec![0.1, 0.5, 1.0, 2.0, 5.0, 10.0, 30.0]
+            )).unwrap(),
+            request_cycles: Histogram::with_opts(histogram_opts!(
+                "prover_request_cycles",
+                "zkVM cycles of the calls of a proof request.",
+                vec![1e5, 1e6, 1e7, 1e8, 1e9, 1e10]
+            )).unwrap(),
+            batch_calls: Histogram::with_opts(histogram_opts!(
+                "prover_batch_calls",
+                "Calls of a batch when it's closed for proving.",
+                vec![1.0, 2.0, 4.0, 8.0, 16.0, 32.0, 64.0, 128.0]
+            )).unwrap(),
+            submissions: IntCounterVec::new(
+                opts!("prover_submissions_total", "Submissions of the batch roots to the oracles."),
+                &["chain_id", "result"],
+            ).unwrap(),
+            submission_gas: CounterVec::new(
+                opts!("prover_submission_gas_used_total", "Gas used by the submitted transactions."),
+                &["chain_id"],
+            ).unwrap(),
+            submission_fees: CounterVec::new(
+                opts!("prover_submission_fees_wei_total", "Fees paid for the submitted transactions, in wei."),
+                &["chain_id"],
+            ).unwrap(),
+            rpc_errors: IntCounterVec::new(
+                opts!("prover_rpc_errors_total", "Failed JSON-RPC requests to the chain nodes."),
+                &["chain_id", "source"],
+            ).unwrap(),
+            queue_depth: IntGauge::new("prover_queue_depth", "Closed batches waiting for a worker.").unwrap(),
+            batches: IntGaugeVec::new(opts!("prover_batches", "Batches by status."), &["status"]).unwrap(),
+            jobs: IntGaugeVec::new(opts!("prover_jobs", "Jobs by status."), &["status"]).unwrap(),
+            registry,
+        };
+        metrics.registry.register(Box::new(metrics.queue_depth.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.batches.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.jobs.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.proving_duration.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.execution_duration.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.request_cycles.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.batch_calls.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.submissions.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.submission_gas.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.submission_fees.clone())).unwrap();
+        metrics.registry.register(Box::new(metrics.rpc_errors.clone())).unwrap();
+        metrics
+    }
+}
+
+/// Counts the failed request and returns its error.
+pub fn rpc_error(chain_id: u64, source: &str, err: impl ToString) -> String {
+    METRICS.rpc_errors.with_label_values(&[&chain_id.to_string(), source]).inc();
+    err.to_string()
+}
+
+/// Sets a sample for every status, including the ones without jobs or batches.
+fn set_status_gauge<S: Serialize + PartialEq>(gauge: &IntGaugeVec, statuses: &[S], counts: &[S]) {
+    for status in statuses {
+        let label = serde_json::to_value(status).unwrap();
+        let count = counts.iter().filter(|counted| *counted == status).count();
+        gauge.with_label_values(&[label.as_str().unwrap_or_default()]).set(count as i64);
+    }
+}
+
+// Handler for /metrics
+pub async fn metrics(State(state): State<Arc<AppState>>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // The jobs are locked before the batches, as in the handlers.
+    let jobs = state.submitted_proofs.lock().unwrap();
+    let (batch_statuses, job_statuses) = state.batcher.with_batches(|batches| (
+        batches.values().map(|batch| batch.status).collect::<Vec<_>>(),
+        jobs.values()
+            .filter_map(|job| match job.batch_id {
+                Some(batch_id) => batches.get(&batch_id).map(|batch| job.status(Some(batch))),
+                None => Some(job.status(None)),
+            })
+            .collect::<Vec<_>>(),
+    ));
+    drop(jobs);
+
+    let queued = batch_statuses.iter().filter(|status| **status == BatchStatus::Queued).count();
+    METRICS.queue_depth.set(queued as i64);
+    set_status_gauge(
+        &METRICS.batches,
+        &[BatchStatus::Open, BatchStatus::Queued, BatchStatus::Proving, BatchStatus::Proven, BatchStatus::Failed],
+        &batch_statuses,
+    );
+    set_status_gauge(
+        &METRICS.jobs,
+        &[JobStatus::Pending, JobStatus::Proving, JobStatus::Proven, JobStatus::Submitted, JobStatus::Failed],
+        &job_statuses,
+    );
+
+    let encoder = TextEncoder::new();
+    encoder.encode_to_string(&METRICS.registry.gather())
+        .map(|out| ([(header::CONTENT_TYPE, encoder.format_type().to_string())], out))
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposition() {
+        rpc_error(1, "test", "error");
+        METRICS.proving_duration.with_label_values(&["ok"]).observe(20.0);
+        let out = TextEncoder::new().encode_to_string(&METRICS.registry.gather()).unwrap();
+        assert!(out.contains("# TYPE prover_rpc_errors_total counter"));
+        assert!(out.contains(r#"prover_rpc_errors_total{chain_id="1",source="test"} 1"#));
+        assert!(out.contains(r#"prover_proving_duration_seconds_bucket{result="ok",le="30"} 1"#));
+        assert!(out.contains(r#"prover_proving_duration_seconds_bucket{result="ok",le="10"} 0"#));
+    }
+}
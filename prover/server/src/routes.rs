@@ -0,0 +1,454 @@
+use axum::{
+    extract::{Extension, Path, Json, State},
+    http::StatusCode,
+    middleware,
+    routing::{get, post},
+    Router,
+};
+use std::sync::Arc;
+use std::time::Instant;
+use web3::types::{Bytes, CallRequest, H256};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, SecurityScheme};
+use utoipa::{Modify, OpenApi};
+use solc_zkmod_api::{
+    AddBytecodeRequest, AddBytecodeResponse, CallWitness, CheckProofResponse, EstimateRequest, EstimateResponse,
+    JobEvent, JobStatus, ProofRequest, ProofRequestData, ProofResponse, Submission, TransactionWitness, Webhook,
+    WitnessOutput, API_KEY_HEADER,
+};
+use solc_zkmod_calldata::{build_calldata, encode_witness, Witness};
+use solc_zkmod_lib::{keccak256, leaf_hash, run_evm_bounded, ExecutionStatus, MAX_STEPS};
+use solc_zkmod_lib::evm::analysis::forbidden_opcodes;
+use solc_zkmod_lib::batch::ProvingBatch;
+use crate::admin::{self, BatchDump, BatchSummary, JobSummary, RequestDump, ADMIN_TOKEN_HEADER};
+use crate::auth::{authenticate, charge, charge_execution, check_quota, AuthenticatedKey};
+use crate::batcher::{BatchStatus, ExecutedBatch};
+use crate::chain::make_eth_call;
+use crate::events::{job_events, notify_webhook};
+use crate::metrics::{metrics, METRICS};
+use crate::state::{AppState, Call, Job, Transaction};
+
+/// Limit of the `@free` calls discovered in a single transaction.
+const MAX_FREE_CALLS: usize = 32;
+
+// Handler for /request-proof
+#[utoipa::path(
+    post,
+    path = "/request-proof",
+    request_body = ProofRequest,
+    responses(
+        (status = 200, description = "The calls are queued for proving", body = ProofResponse),
+        (status = 400, description = "Unsupported chain or a transaction without provable @free calls", body = String),
+        (status = 403, description = "Cycle quota exceeded", body = String),
+        (status = 404, description = "Unknown bytecode of a discovered call", body = String),
+        (status = 422, description = "A call can't be proven", body = String),
+        (status = 503, description = "The server is shutting down", body = String),
+    ),
+    security(("api_key" = [])),
+)]
+#[tracing::instrument(skip_all, fields(chain_id = payload.chain_id, job_id = tracing::field::Empty))]
+async fn request_proof(
+    State(state): State<Arc<AppState>>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Json(payload): Json<ProofRequest>,
+) -> Result<Json<ProofResponse>, (StatusCode, String)> {
+    if state.batcher.is_shutting_down() {
+        return Err((StatusCode::SERVICE_UNAVAILABLE, "The server is shutting down".to_string()));
+    }
+    let chain = state.chains
+        .get(&payload.chain_id)
+        .ok_or((StatusCode::BAD_REQUEST, format!("Unsupported chain {}", payload.chain_id)))?;
+    // Rejected before the simulations and executions, charged for their cycles at the end.
+    state.store.read(|data| check_quota(data, &key))?;
+    let mut batch = ProvingBatch::default();
+    let mut transactions = vec![];
+
+    for req in payload.requests {
+        // Simulate the transaction until all its `@free` calls are discovered, the outcome of
+        // every discovered call is appended to the calldata without a proof for the next round.
+        let mut witnesses: Vec<Witness> = vec![];
+        let mut calls = vec![];
+        loop {
+            let call_request = CallRequest {
+                from: Some(req.sender),
+                to: Some(req.address),
+                value: Some(req.value),
+                data: Some(Bytes(build_calldata(&req.calldata.0, &witnesses))),
+                ..Default::default()
+            };
+            let Some(discovery) = make_eth_call(chain.backend.as_ref(), call_request, chain.oracle)
+                .await
+                .map_err(|err| {
+                    tracing::info!(error = %err, "Simulation failed");
+                    (StatusCode::BAD_REQUEST, err)
+                })?
+            else {
+                break;
+            };
+            tracing::debug!(code_hash = %hex::encode(discovery.code_hash), "Discovered a @free call");
+            let input_hash = keccak256(discovery.input.as_slice());
+            if witnesses.iter().any(|witness| witness.code_hash == discovery.code_hash && witness.input_hash == input_hash) {
+                return Err((StatusCode::BAD_REQUEST, "Discovered an already executed call".to_string()));
+            }
+            if witnesses.len() == MAX_FREE_CALLS {
+                return Err((StatusCode::BAD_REQUEST, format!("More than {} @free calls", MAX_FREE_CALLS)));
+            }
+
+            let bytecode = state.store
+                .bytecode(&H256(discovery.code_hash))
+                .map(|bytecode| bytecode.0)
+                .ok_or((StatusCode::NOT_FOUND, format!("Unknown bytecode 0x{}", hex::encode(discovery.code_hash))))?;
+            let calldata = discovery.input;
+            let (bytecode, calldata, outcome) = tokio::task::spawn_blocking(move || {
+                let outcome = run_evm_bounded(&bytecode, &calldata, MAX_STEPS);
+                (bytecode, calldata, outcome)
+            })
+                .await
+                .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?;
+            let outcome = outcome
+                .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, format!("Call can't be proven: {}", err)))?;
+            let leaf = leaf_hash(discovery.code_hash, &calldata, &outcome);
+            witnesses.push(Witness {
+                code_hash: discovery.code_hash,
+                input_hash,
+                reverted: outcome.status == ExecutionStatus::Revert,
+                output: outcome.output,
+                proof: vec![],
+            });
+
+            // A call already proven under a root recorded on the chain isn't proven again.
+            match state.batcher.proven_call(&leaf, payload.chain_id) {
+                Some(proof) => calls.push(Call::Proven(Witness { proof, ..witnesses.last().unwrap().clone() })),
+                None => {
+                    calls.push(Call::Batch(batch.requests.len()));
+                    batch.push(&bytecode, calldata);
+                }
+            }
+        }
+        if calls.is_empty() {
+            return Err((StatusCode::BAD_REQUEST, "No @free calls to prove".to_string()));
+        }
+        transactions.push(Transaction {
+            calldata: req.calldata.0,
+            calls,
+        });
+    }
+
+    // The cycles are counted by executing the program, it's much cheaper than proving.
+    let mut cycles = 0;
+    if !batch.requests.is_empty() {
+        let prover = state.prover.clone();
+        let executed_batch = batch.clone();
+        let started_at = Instant::now();
+        cycles = tokio::task::spawn_blocking(move || prover.cycles(&executed_batch))
+            .await
+            .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+            .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, err))?;
+        METRICS.execution_duration.observe(started_at.elapsed().as_secs_f64());
+    }
+    METRICS.request_cycles.observe(cycles as f64);
+    state.store
+        .update(|data| charge(data, &key, batch.requests.len() as u64, cycles))
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))??;
+
+    // Random, transactions making the same `@free` calls are separate jobs.
+    let req_id = hex::encode(rand::random::<[u8; 32]>());
+    tracing::Span::current().record("job_id", req_id.as_str());
+    let mut batch_id = None;
+    if !batch.requests.is_empty() {
+        let (id, offset) = state.batcher.add(payload.chain_id, &req_id, &batch);
+        for call in transactions.iter_mut().flat_map(|transaction| &mut transaction.calls) {
+            if let Call::Batch(index) = call {
+                *index += offset;
+            }
+        }
+        batch_id = Some(id);
+    }
+    let proven_calls = transactions.iter()
+        .flat_map(|transaction| &transaction.calls)
+        .filter(|call| matches!(call, Call::Proven(_)))
+        .count();
+    tracing::info!(batch_id, calls = batch.requests.len(), proven_calls, cycles, "Job queued");
+
+    state.add_job(req_id.clone(), Job {
+        chain_id: payload.chain_id,
+        batch_id,
+        webhook: payload.webhook.clone(),
+        transactions,
+        delivered: None,
+    });
+    // Jobs without a batch are done already, no batch event will notify their webhook.
+    if batch_id.is_none() && payload.webhook.is_some() {
+        notify_webhook(state.clone(), req_id.clone());
+    }
+
+    Ok(Json(ProofResponse { id: req_id }))
+}
+
+// Handler for /get-witness/{id}
+#[utoipa::path(
+    get,
+    path = "/get-witness/{id}",
+    params(("id" = String, Path, description = "Job id")),
+    responses(
+        (status = 200, description = "Witnesses of the job transactions", body = WitnessOutput),
+        (status = 404, description = "Unknown id or the proof is not ready", body = String),
+        (status = 500, description = "Proving failed", body = String),
+    ),
+    security(("api_key" = [])),
+)]
+#[tracing::instrument(skip_all, fields(job_id = %id))]
+async fn get_witness(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<WitnessOutput>, (StatusCode, String)> {
+    let job = state.submitted_proofs.lock().unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or((StatusCode::NOT_FOUND, "Unknown id".to_string()))?;
+    let executed = match job.batch_id {
+        Some(batch_id) => Some(state.batcher
+            .with_batch(batch_id, |batch| match (&batch.executed, &batch.error) {
+                (Some(executed), _) => Ok(executed.clone()),
+                (None, Some(err)) => Err((StatusCode::INTERNAL_SERVER_ERROR, err.clone())),
+                (None, None) => Err((StatusCode::NOT_FOUND, "Proof is not ready".to_string())),
+            })
+            .ok_or((StatusCode::NOT_FOUND, "Unknown batch".to_string()))??),
+        None => None,
+    };
+
+    let witnesses = job_witnesses(&job, executed.as_deref())?;
+    Ok(Json(WitnessOutput { witnesses }))
+}
+
+/// Witnesses of the job transactions, `executed` is the job batch once it's proven.
+pub(crate) fn job_witnesses(
+    job: &Job,
+    executed: Option<&ExecutedBatch>,
+) -> Result<Vec<TransactionWitness>, (StatusCode, String)> {
+    let mut witnesses = vec![];
+    for transaction in &job.transactions {
+        let mut entries = vec![];
+        for call in &transaction.calls {
+            let index = match call {
+                Call::Proven(witness) => {
+                    entries.push(witness.clone());
+                    continue;
+                }
+                Call::Batch(index) => *index,
+            };
+            let Some(executed) = executed else {
+                return Err((StatusCode::NOT_FOUND, "Proof is not ready".to_string()));
+            };
+            let Some((_, witness)) = &executed.calls[index] else {
+                return Err((StatusCode::UNPROCESSABLE_ENTITY, "Request can't be proven".to_string()));
+            };
+            entries.push(witness.clone());
+        }
+        witnesses.push(TransactionWitness {
+            witness: Bytes(encode_witness(transaction.calldata.len(), &entries)),
+            calldata: Bytes(build_calldata(&transaction.calldata, &entries)),
+            calls: entries.into_iter().map(|entry| CallWitness {
+                code_hash: H256(entry.code_hash),
+                status: entry.reverted as u8,
+                output: Bytes(entry.output),
+                merkle_proof: entry.proof.into_iter().map(H256).collect(),
+            }).collect(),
+        });
+    }
+    Ok(witnesses)
+}
+
+// Handler for /add-bytecode, the bytecode is rejected if it contains forbidden opcodes, even
+// unreachable ones
+#[utoipa::path(
+    post,
+    path = "/add-bytecode",
+    request_body = AddBytecodeRequest,
+    responses(
+        (status = 200, body = AddBytecodeResponse),
+        (status = 422, description = "Forbidden opcodes", body = String),
+    ),
+    security(("api_key" = [])),
+)]
+async fn add_bytecode(
+    State(state): State<Arc<AppState>>,
+    Json(payload): Json<AddBytecodeRequest>,
+) -> Result<Json<AddBytecodeResponse>, (StatusCode, String)> {
+    check_bytecode(&payload.bytecode.0)?;
+
+    let code_hash = state.store
+        .add_bytecode(payload.bytecode)
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))?;
+
+    Ok(Json(AddBytecodeResponse { code_hash }))
+}
+
+/// Rejects the bytecode with forbidden opcodes, the same way for the registration and the estimates.
+fn check_bytecode(bytecode: &[u8]) -> Result<(), (StatusCode, String)> {
+    let forbidden = forbidden_opcodes(bytecode);
+    if !forbidden.is_empty() {
+        let opcodes = forbidden.iter()
+            .map(|forbidden| format!("0x{:02x} at pc {}", forbidden.opcode, forbidden.pc))
+            .collect::<Vec<_>>();
+        return Err((StatusCode::UNPROCESSABLE_ENTITY, format!("Forbidden opcodes: {}", opcodes.join(", "))));
+    }
+    Ok(())
+}
+
+// Handler for /check-proof/{id}
+#[utoipa::path(
+    get,
+    path = "/check-proof/{id}",
+    params(("id" = String, Path, description = "Job id")),
+    responses((status = 200, body = CheckProofResponse)),
+    security(("api_key" = [])),
+)]
+#[tracing::instrument(skip_all, fields(job_id = %id))]
+async fn check_proof(
+    Path(id): Path<String>,
+    State(state): State<Arc<AppState>>,
+) -> Json<CheckProofResponse> {
+    let jobs = state.submitted_proofs.lock().unwrap();
+    let response = jobs.get(&id).and_then(|job| match job.batch_id {
+        Some(batch_id) => state.batcher.with_batch(batch_id, |batch| CheckProofResponse {
+            exists: batch.proof.is_some(),
+            status: Some(job.status(Some(batch))),
+            submission: job.submission(Some(batch)),
+        }),
+        None => Some(CheckProofResponse {
+            exists: true,
+            status: Some(job.status(None)),
+            submission: job.submission(None),
+        }),
+    });
+    Json(response.unwrap_or(CheckProofResponse { exists: false, status: None, submission: None }))
+}
+
+// Handler for /estimate, compares the gas of the call executed on-chain with its `@free` cost in
+// the open batch of the chain
+#[utoipa::path(
+    post,
+    path = "/estimate",
+    request_body = EstimateRequest,
+    responses(
+        (status = 200, body = EstimateResponse),
+        (status = 400, description = "Unsupported chain", body = String),
+        (status = 403, description = "Cycle quota exceeded", body = String),
+        (status = 422, description = "Forbidden opcodes, or the call can't be proven", body = String),
+    ),
+    security(("api_key" = [])),
+)]
+#[tracing::instrument(skip_all, fields(chain_id = payload.chain_id))]
+async fn estimate(
+    State(state): State<Arc<AppState>>,
+    Extension(AuthenticatedKey(key)): Extension<AuthenticatedKey>,
+    Json(payload): Json<EstimateRequest>,
+) -> Result<Json<EstimateResponse>, (StatusCode, String)> {
+    if !state.chains.contains_key(&payload.chain_id) {
+        return Err((StatusCode::BAD_REQUEST, format!("Unsupported chain {}", payload.chain_id)));
+    }
+    check_bytecode(&payload.bytecode.0)?;
+    // The executions count against the cycle quota the same way as the proof requests.
+    state.store.read(|data| check_quota(data, &key))?;
+    let batch_calls = state.batcher.open_calls(payload.chain_id) as u64 + 1;
+    let prover = state.prover.clone();
+    let estimate = tokio::task::spawn_blocking(move || {
+        solc_zkmod_lib::estimate::estimate(&prover, &payload.bytecode.0, &payload.calldata.0, batch_calls)
+    })
+        .await
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err.to_string()))?
+        .map_err(|err| (StatusCode::UNPROCESSABLE_ENTITY, format!("Call can't be proven: {}", err)))?;
+    state.store
+        .update(|data| charge_execution(data, &key, estimate.cycles))
+        .map_err(|err| (StatusCode::INTERNAL_SERVER_ERROR, err))??;
+
+    Ok(Json(EstimateResponse {
+        native_gas: estimate.native_gas,
+        cycles: estimate.cycles,
+        batch_calls: estimate.batch_calls,
+        verification_gas: estimate.verification_gas,
+        overhead_gas: estimate.overhead_gas,
+        free_gas: estimate.free_gas,
+        worth_offloading: estimate.worth_offloading,
+    }))
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "solc-zkmod prover server"),
+    paths(
+        request_proof,
+        check_proof,
+        add_bytecode,
+        get_witness,
+        estimate,
+        crate::events::job_events,
+        crate::admin::list_jobs,
+        crate::admin::list_batches,
+        crate::admin::get_batch,
+        crate::admin::retry_batch,
+        crate::admin::cancel_batch,
+        crate::admin::close_batch,
+    ),
+    components(schemas(
+        AddBytecodeRequest,
+        AddBytecodeResponse,
+        ProofRequest,
+        ProofRequestData,
+        Webhook,
+        ProofResponse,
+        WitnessOutput,
+        TransactionWitness,
+        CallWitness,
+        CheckProofResponse,
+        EstimateRequest,
+        EstimateResponse,
+        JobStatus,
+        Submission,
+        JobEvent,
+        JobSummary,
+        BatchStatus,
+        BatchSummary,
+        BatchDump,
+        RequestDump,
+    )),
+    modifiers(&ApiKeySecurity),
+)]
+pub struct ApiDoc;
+
+struct ApiKeySecurity;
+
+impl Modify for ApiKeySecurity {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))),
+        );
+        components.add_security_scheme(
+            "admin_token",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(ADMIN_TOKEN_HEADER))),
+        );
+    }
+}
+
+async fn openapi() -> Json<utoipa::openapi::OpenApi> {
+    Json(ApiDoc::openapi())
+}
+
+/// The admin routes are enabled by the admin token.
+pub fn router(state: Arc<AppState>, admin_token: Option<String>) -> Router {
+    Router::new()
+        .route("/request-proof", post(request_proof))
+        .route("/check-proof/:id", get(check_proof))
+        .route("/add-bytecode", post(add_bytecode))
+        .route("/get-witness/:id", get(get_witness))
+        .route("/estimate", post(estimate))
+        .route("/jobs/:id/events", get(job_events))
+        .layer(middleware::from_fn_with_state(state.clone(), authenticate))
+        .nest("/admin", admin::router(admin_token))
+        // The spec and the metrics don't require a key.
+        .route("/openapi.json", get(openapi))
+        .route("/metrics", get(metrics))
+        .with_state(state)
+}
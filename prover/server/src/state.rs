@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use serde::{Deserialize, Serialize};
+use solc_zkmod_api::{JobStatus, Submission, Webhook};
+use solc_zkmod_calldata::Witness;
+use solc_zkmod_lib::prover::prover::Prover;
+use crate::auth::RateLimiter;
+use crate::batcher::{Batch, BatchStatus, Batcher};
+use crate::chain::Chain;
+use crate::store::Store;
+
+const JOB_RECORDS: &str = "jobs";
+
+// Shared state to keep track of submitted proofs
+pub struct AppState {
+    pub submitted_proofs: Mutex<HashMap<String, Job>>,
+    pub batcher: Arc<Batcher>,
+    /// Supported chains by their id.
+    pub chains: HashMap<u64, Chain>,
+    pub prover: Arc<Prover>,
+    pub store: Arc<Store>,
+    pub rate_limiter: RateLimiter,
+}
+
+impl AppState {
+    pub fn new(chains: HashMap<u64, Chain>, prover: Arc<Prover>, batcher: Arc<Batcher>, store: Arc<Store>) -> Self {
+        Self {
+            submitted_proofs: Mutex::new(HashMap::new()),
+            batcher,
+            chains,
+            prover,
+            store,
+            rate_limiter: RateLimiter::default(),
+        }
+    }
+
+    /// Loads the jobs saved before the restart, their batches are recovered by the batcher.
+    pub fn recover_jobs(&self) -> Result<usize, String> {
+        let jobs = self.store.load_records::<Job>(JOB_RECORDS)?;
+        let count = jobs.len();
+        self.submitted_proofs.lock().unwrap().extend(jobs);
+        Ok(count)
+    }
+
+    /// Inserts the job and saves it, so it survives a restart.
+    pub fn add_job(&self, id: String, job: Job) {
+        if let Err(err) = self.store.save_record(JOB_RECORDS, &id, &job) {
+            tracing::error!(job_id = %id, error = %err, "Failed to save the job");
+        }
+        self.submitted_proofs.lock().unwrap().insert(id, job);
+    }
+
+    /// Records the status delivered to the webhook of the job, `false` if it was delivered before.
+    pub fn mark_delivered(&self, id: &str, status: JobStatus) -> bool {
+        let mut jobs = self.submitted_proofs.lock().unwrap();
+        let Some(job) = jobs.get_mut(id) else {
+            return false;
+        };
+        if job.delivered == Some(status) {
+            return false;
+        }
+        job.delivered = Some(status);
+        if let Err(err) = self.store.save_record(JOB_RECORDS, id, &*job) {
+            tracing::error!(job_id = %id, error = %err, "Failed to save the job");
+        }
+        true
+    }
+}
+
+/// Transactions of a proof request, their new calls are proven in the batch `batch_id`.
+/// A job without a batch has only the calls proven and submitted before.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Job {
+    pub chain_id: u64,
+    pub batch_id: Option<u64>,
+    pub transactions: Vec<Transaction>,
+    pub webhook: Option<Webhook>,
+    /// Last status delivered to the webhook, saved so a status is delivered once, also across
+    /// restarts.
+    #[serde(default)]
+    pub delivered: Option<JobStatus>,
+}
+
+impl Job {
+    pub fn status(&self, batch: Option<&Batch>) -> JobStatus {
+        let Some(batch) = batch else {
+            return JobStatus::Submitted;
+        };
+        match batch.status {
+            BatchStatus::Open | BatchStatus::Queued => JobStatus::Pending,
+            BatchStatus::Proving => JobStatus::Proving,
+            BatchStatus::Failed => JobStatus::Failed,
+            BatchStatus::Proven => match batch.submissions.get(&self.chain_id) {
+                Some(Submission::Submitted { .. }) => JobStatus::Submitted,
+                Some(Submission::Failed { .. }) => JobStatus::Failed,
+                Some(Submission::Pending) | None => JobStatus::Proven,
+            },
+        }
+    }
+
+    pub fn submission(&self, batch: Option<&Batch>) -> Option<Submission> {
+        match batch {
+            Some(batch) => batch.submissions.get(&self.chain_id).cloned(),
+            None => Some(Submission::Submitted { tx_hash: None }),
+        }
+    }
+}
+
+/// Transaction with its `@free` calls, in the order of the calls.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transaction {
+    pub calldata: Vec<u8>,
+    pub calls: Vec<Call>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Call {
+    /// Index of the request in the job batch.
+    Batch(usize),
+    /// Proven in an earlier batch whose root is recorded by the oracle of the job chain.
+    Proven(#[serde(with = "WitnessDef")] Witness),
+}
+
+/// The calldata crate has no serde dependency.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "Witness")]
+struct WitnessDef {
+    code_hash: [u8; 32],
+    input_hash: [u8; 32],
+    reverted: bool,
+    output: Vec<u8>,
+    proof: Vec<[u8; 32]>,
+}
@@ -0,0 +1,179 @@
+//! Persistent server data, saved as a JSON file in the storage directory after every change.
+//! The jobs, batches and bytecodes are records with a file each, so saving one doesn't rewrite the
+//! others.
+
+use std::collections::hash_map::{Entry, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use web3::types::{Bytes, H256};
+use solc_zkmod_lib::keccak256;
+
+const STORE_FILE: &str = "store.json";
+const BYTECODES: &str = "bytecodes";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StoreData {
+    /// API keys by the key itself.
+    pub api_keys: HashMap<String, ApiKey>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApiKey {
+    /// Name of the key owner, e.g. the team.
+    pub name: String,
+    /// Requests per minute, unlimited if `None`.
+    pub rate_limit: Option<u32>,
+    /// Total zkVM cycles of the proven requests, unlimited if `None`.
+    pub cycle_quota: Option<u64>,
+    #[serde(default)]
+    pub usage: Usage,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    pub proof_requests: u64,
+    pub calls: u64,
+    pub cycles: u64,
+}
+
+pub struct Store {
+    path: Option<PathBuf>,
+    data: Mutex<StoreData>,
+    /// Registered bytecodes by their keccak hash.
+    bytecodes: Mutex<HashMap<H256, Bytes>>,
+}
+
+impl Store {
+    /// Loads the store from the directory, or creates an empty one.
+    pub fn open(dir: PathBuf) -> Result<Self, String> {
+        std::fs::create_dir_all(&dir).map_err(|err| err.to_string())?;
+        let path = dir.join(STORE_FILE);
+        let data = match std::fs::read_to_string(&path) {
+            Ok(data) => serde_json::from_str(&data).map_err(|err| format!("Invalid {}: {}", path.display(), err))?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => StoreData::default(),
+            Err(err) => return Err(err.to_string()),
+        };
+        let store = Self { path: Some(path), data: Mutex::new(data), bytecodes: Mutex::default() };
+        let bytecodes = store.load_records::<Bytes>(BYTECODES)?
+            .into_iter()
+            .map(|(_, bytecode)| (H256(keccak256(&bytecode.0)), bytecode))
+            .collect();
+        *store.bytecodes.lock().unwrap() = bytecodes;
+        Ok(store)
+    }
+
+    /// Store that is not saved anywhere.
+    pub fn in_memory() -> Self {
+        Self { path: None, data: Mutex::default(), bytecodes: Mutex::default() }
+    }
+
+    pub fn read<R>(&self, f: impl FnOnce(&StoreData) -> R) -> R {
+        f(&self.data.lock().unwrap())
+    }
+
+    /// Applies the change and saves the store.
+    pub fn update<R>(&self, f: impl FnOnce(&mut StoreData) -> R) -> Result<R, String> {
+        let mut data = self.data.lock().unwrap();
+        let result = f(&mut data);
+        if let Some(path) = &self.path {
+            write_json(path, &*data).map_err(|err| format!("Failed to save the store: {}", err))?;
+        }
+        Ok(result)
+    }
+
+    pub fn bytecode(&self, code_hash: &H256) -> Option<Bytes> {
+        self.bytecodes.lock().unwrap().get(code_hash).cloned()
+    }
+
+    /// Registers the bytecode, saved as the record `storage/bytecodes/{hash}.json` the first time.
+    pub fn add_bytecode(&self, bytecode: Bytes) -> Result<H256, String> {
+        let code_hash = H256(keccak256(&bytecode.0));
+        let mut bytecodes = self.bytecodes.lock().unwrap();
+        if let Entry::Vacant(entry) = bytecodes.entry(code_hash) {
+            self.save_record(BYTECODES, &hex::encode(code_hash), &bytecode)?;
+            entry.insert(bytecode);
+        }
+        Ok(code_hash)
+    }
+
+    /// Saves the record `storage/{kind}/{id}.json`, replacing the previous version.
+    pub fn save_record<T: Serialize>(&self, kind: &str, id: &str, record: &T) -> Result<(), String> {
+        let Some(dir) = self.records_dir(kind) else {
+            return Ok(());
+        };
+        std::fs::create_dir_all(&dir)
+            .map_err(|err| err.to_string())
+            .and_then(|_| write_json(&dir.join(format!("{}.json", id)), record))
+            .map_err(|err| format!("Failed to save {}/{}: {}", kind, id, err))
+    }
+
+    /// All the records of the kind with their ids.
+    pub fn load_records<T: DeserializeOwned>(&self, kind: &str) -> Result<Vec<(String, T)>, String> {
+        let Some(dir) = self.records_dir(kind) else {
+            return Ok(vec![]);
+        };
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(vec![]),
+            Err(err) => return Err(err.to_string()),
+        };
+        let mut records = vec![];
+        for entry in entries {
+            let path = entry.map_err(|err| err.to_string())?.path();
+            // Skips the temporary files of an interrupted write.
+            if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+                continue;
+            }
+            let Some(id) = path.file_stem().and_then(|id| id.to_str()) else {
+                continue;
+            };
+            let data = std::fs::read_to_string(&path).map_err(|err| err.to_string())?;
+            let record = serde_json::from_str(&data).map_err(|err| format!("Invalid {}: {}", path.display(), err))?;
+            records.push((id.to_string(), record));
+        }
+        Ok(records)
+    }
+
+    fn records_dir(&self, kind: &str) -> Option<PathBuf> {
+        Some(self.path.as_ref()?.parent()?.join(kind))
+    }
+}
+
+/// Written to a temporary file first, so the file is never left half-written.
+fn write_json<T: Serialize + ?Sized>(path: &Path, value: &T) -> Result<(), String> {
+    let encoded = serde_json::to_string_pretty(value).map_err(|err| err.to_string())?;
+    let tmp_path = path.with_extension("tmp");
+    std::fs::write(&tmp_path, encoded)
+        .and_then(|_| std::fs::rename(&tmp_path, path))
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn storage_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("solc-zkmod-store-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn bytecode_records() {
+        let dir = storage_dir("bytecodes");
+        let store = Store::open(dir.clone()).unwrap();
+        let bytecode = Bytes(vec![0x5f, 0x5f, 0xf3]);
+        let code_hash = store.add_bytecode(bytecode.clone()).unwrap();
+        assert_eq!(code_hash, H256(keccak256(&bytecode.0)));
+        assert_eq!(store.add_bytecode(bytecode.clone()).unwrap(), code_hash);
+        assert_eq!(store.bytecode(&code_hash), Some(bytecode.clone()));
+        assert!(dir.join(BYTECODES).join(format!("{}.json", hex::encode(code_hash))).exists());
+        assert!(!std::fs::read_to_string(dir.join(STORE_FILE)).unwrap_or_default().contains("bytecodes"));
+
+        let reopened = Store::open(dir.clone()).unwrap();
+        assert_eq!(reopened.bytecode(&code_hash), Some(bytecode));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+//! Posts the proven batch roots to the `ComputationOracle` of their chains.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use alloy_sol_types::{sol, SolCall};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use web3::signing::{Key, SecretKey, SecretKeyRef};
+use web3::transports::Http;
+use web3::types::{Address, BlockNumber, Bytes, CallRequest, TransactionParameters, H256, U64};
+use web3::Web3;
+use crate::config::ChainConfig;
+use crate::metrics::{rpc_error, METRICS};
+
+sol! {
+    function proveComputation(bytes32 merkleRoot, bytes zkp, bool dummyVerifier) external;
+    function proved(bytes32 merkleRoot) external view returns (bool);
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SubmitterConfig {
+    /// Hex-encoded key of the account sending the transactions, it shouldn't be used elsewhere.
+    pub private_key: String,
+    /// Skips the SP1 verification in the oracle, for testing.
+    pub dummy_verifier: bool,
+    /// Retries of a failed or stuck transaction, with a higher gas price each time.
+    pub max_retries: u32,
+    /// Time to wait for the receipt before the transaction is considered stuck.
+    pub receipt_timeout_secs: u64,
+}
+
+impl Default for SubmitterConfig {
+    fn default() -> Self {
+        Self {
+            private_key: String::new(),
+            dummy_verifier: false,
+            max_retries: 3,
+            receipt_timeout_secs: 120,
+        }
+    }
+}
+
+struct ChainSubmitter {
+    chain_id: u64,
+    web3: Web3<Http>,
+    oracle: Address,
+    /// Serializes the transactions of the chain, so every one gets the next nonce.
+    lock: Mutex<()>,
+}
+
+pub struct Submitter {
+    config: SubmitterConfig,
+    key: SecretKey,
+    chains: HashMap<u64, ChainSubmitter>,
+}
+
+impl Submitter {
+    pub fn new(config: SubmitterConfig, chains: &[ChainConfig]) -> Result<Self, String> {
+        let key = SecretKey::from_str(config.private_key.trim_start_matches("0x"))
+            .map_err(|err| format!("Invalid submitter key: {}", err))?;
+        let chains = chains.iter().map(|chain| {
+            let transport = Http::new(&chain.rpc_url).map_err(|err| err.to_string())?;
+            Ok((chain.chain_id, ChainSubmitter {
+                chain_id: chain.chain_id,
+                web3: Web3::new(transport),
+                oracle: chain.oracle,
+                lock: Mutex::new(()),
+            }))
+        }).collect::<Result<_, String>>()?;
+        Ok(Self { config, key, chains })
+    }
+
+    pub fn address(&self) -> Address {
+        SecretKeyRef::new(&self.key).address()
+    }
+
+    ///
+    /// Records the root in the oracle of the chain, returns the hash of the transaction.
+    /// Returns `None` if the root is already recorded, e.g. by another batch with the same calls.
+    ///
+    pub async fn submit(&self, chain_id: u64, root: [u8; 32], proof: Vec<u8>) -> Result<Option<H256>, String> {
+        let chain = self.chains.get(&chain_id).ok_or(format!("Unsupported chain {}", chain_id))?;
+        let data = proveComputationCall {
+            merkleRoot: root.into(),
+            zkp: proof.into(),
+            dummyVerifier: self.config.dummy_verifier,
+        }.abi_encode();
+
+        let _lock = chain.lock.lock().await;
+        let result = self.submit_locked(chain, root, &data).await;
+        let outcome = match &result {
+            Ok(Some(_)) => "submitted",
+            Ok(None) => "already_proved",
+            Err(_) => "failed",
+        };
+        METRICS.submissions.with_label_values(&[&chain_id.to_string(), outcome]).inc();
+        result
+    }
+
+    async fn submit_locked(&self, chain: &ChainSubmitter, root: [u8; 32], data: &[u8]) -> Result<Option<H256>, String> {
+        let mut error = String::new();
+        for attempt in 0..=self.config.max_retries {
+            if self.is_proved(chain, root).await? {
+                return Ok(None);
+            }
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_secs(1 << attempt.min(5))).await;
+            }
+            match self.send(chain, data, attempt).await {
+                Ok(hash) => return Ok(Some(hash)),
+                Err(err) => {
+                    tracing::warn!(attempt, error = %err, "Submission attempt failed");
+                    error = err;
+                }
+            }
+        }
+        Err(format!("Failed to submit the root after {} retries: {}", self.config.max_retries, error))
+    }
+
+    async fn is_proved(&self, chain: &ChainSubmitter, root: [u8; 32]) -> Result<bool, String> {
+        let request = CallRequest {
+            to: Some(chain.oracle),
+            data: Some(Bytes(provedCall { merkleRoot: root.into() }.abi_encode())),
+            ..Default::default()
+        };
+        let result = chain.web3.eth().call(request, None).await.map_err(|err| rpc_error(chain.chain_id, "submitter", err))?;
+        provedCall::abi_decode_returns(&result.0, true)
+            .map(|proved| proved._0)
+            .map_err(|err| err.to_string())
+    }
+
+    ///
+    /// Sends the transaction and waits for its receipt.
+    /// The nonce is the count of the mined transactions, so a retry replaces the stuck transaction.
+    ///
+    async fn send(&self, chain: &ChainSubmitter, data: &[u8], attempt: u32) -> Result<H256, String> {
+        let eth = chain.web3.eth();
+        let nonce = eth.transaction_count(self.address(), Some(BlockNumber::Latest))
+            .await
+            .map_err(|err| rpc_error(chain.chain_id, "submitter", err))?;
+        let gas = eth.estimate_gas(CallRequest {
+            from: Some(self.address()),
+            to: Some(chain.oracle),
+            data: Some(Bytes(data.to_vec())),
+            ..Default::default()
+        }, None).await.map_err(|err| format!("Gas estimation failed: {}", err))?;
+        // The replacement must pay at least 10% more, so every retry adds 20%.
+        let gas_price = eth.gas_price().await.map_err(|err| rpc_error(chain.chain_id, "submitter", err))?;
+        let gas_price = gas_price * (100 + 20 * attempt) / 100;
+
+        let transaction = TransactionParameters {
+            nonce: Some(nonce),
+            to: Some(chain.oracle),
+            gas: gas * 6 / 5,
+            gas_price: Some(gas_price),
+            data: Bytes(data.to_vec()),
+            chain_id: Some(chain.chain_id),
+            ..Default::default()
+        };
+        let signed = chain.web3.accounts()
+            .sign_transaction(transaction, &self.key)
+            .await
+            .map_err(|err| err.to_string())?;
+        let hash = eth.send_raw_transaction(signed.raw_transaction)
+            .await
+            .map_err(|err| rpc_error(chain.chain_id, "submitter", err))?;
+        tracing::info!(tx_hash = ?hash, nonce = %nonce, gas_price = %gas_price, "Transaction sent");
+
+        let timeout = Duration::from_secs(self.config.receipt_timeout_secs);
+        let sent_at = Instant::now();
+        while sent_at.elapsed() < timeout {
+            let receipt = eth.transaction_receipt(hash)
+                .await
+                .map_err(|err| rpc_error(chain.chain_id, "submitter", err))?;
+            let Some(receipt) = receipt else {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                continue;
+            };
+            // A reverted transaction is paid for as well.
+            let gas_used = receipt.gas_used.unwrap_or_default();
+            let fee = gas_used * receipt.effective_gas_price.unwrap_or(gas_price);
+            let chain_id = chain.chain_id.to_string();
+            METRICS.submission_gas.with_label_values(&[&chain_id]).inc_by(gas_used.low_u128() as f64);
+            METRICS.submission_fees.with_label_values(&[&chain_id]).inc_by(fee.low_u128() as f64);
+            return match receipt.status {
+                Some(status) if status == U64::one() => Ok(hash),
+                _ => Err(format!("Transaction {:?} reverted", hash)),
+            };
+        }
+        Err(format!("Transaction {:?} is not mined in {} seconds", hash, timeout.as_secs()))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex as StdMutex};
+    use axum::{extract::State, routing::post, Json, Router};
+    use serde_json::{json, Value};
+    use web3::types::U256;
+
+    const CHAIN_ID: u64 = 31337;
+    const NONCE: u64 = 7;
+    const GAS_PRICE: u64 = 1_000_000_000;
+
+    /// JSON-RPC node answering the calls of the submitter.
+    #[derive(Default)]
+    struct MockNode {
+        /// Result of the `proved` calls, the last one repeats.
+        proved: VecDeque<bool>,
+        /// Status of the receipt of every sent transaction.
+        statuses: VecDeque<u64>,
+        /// Nonce and gas price of the sent transactions.
+        sent: Vec<(U256, U256)>,
+    }
+
+    async fn rpc(State(node): State<Arc<StdMutex<MockNode>>>, Json(request): Json<Value>) -> Json<Value> {
+        let mut node = node.lock().unwrap();
+        let result = match request["method"].as_str().unwrap() {
+            "eth_call" => {
+                let proved = match node.proved.len() {
+                    1 => node.proved[0],
+                    _ => node.proved.pop_front().unwrap(),
+                };
+                json!(format!("0x{:064x}", proved as u8))
+            }
+            "eth_getTransactionCount" => {
+                assert_eq!(request["params"][1], "latest");
+                json!(format!("0x{:x}", NONCE))
+            }
+            "eth_estimateGas" => json!("0x10000"),
+            "eth_gasPrice" => json!(format!("0x{:x}", GAS_PRICE)),
+            "eth_sendRawTransaction" => {
+                let raw = hex::decode(request["params"][0].as_str().unwrap().trim_start_matches("0x")).unwrap();
+                let transaction = rlp::Rlp::new(&raw);
+                node.sent.push((transaction.val_at(0).unwrap(), transaction.val_at(1).unwrap()));
+                json!(format!("{:?}", H256::from_low_u64_be(node.sent.len() as u64)))
+            }
+            "eth_getTransactionReceipt" => json!({
+                "transactionHash": request["params"][0],
+                "transactionIndex": "0x0",
+                "blockHash": format!("{:?}", H256::zero()),
+                "blockNumber": "0x1",
+                "cumulativeGasUsed": "0x5000",
+                "gasUsed": "0x5000",
+                "contractAddress": null,
+                "logs": [],
+                "status": format!("0x{:x}", node.statuses.pop_front().unwrap()),
+                "root": null,
+                "logsBloom": format!("0x{}", "0".repeat(512)),
+                "effectiveGasPrice": format!("0x{:x}", GAS_PRICE),
+            }),
+            method => panic!("Unexpected {}", method),
+        };
+        Json(json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }))
+    }
+
+    async fn submitter(node: MockNode, max_retries: u32) -> (Submitter, Arc<StdMutex<MockNode>>) {
+        let node = Arc::new(StdMutex::new(node));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let rpc_url = format!("http://{}", listener.local_addr().unwrap());
+        let router = Router::new().route("/", post(rpc)).with_state(node.clone());
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        let config = SubmitterConfig {
+            private_key: format!("0x{}", "11".repeat(32)),
+            max_retries,
+            receipt_timeout_secs: 5,
+            ..Default::default()
+        };
+        let chain = ChainConfig { chain_id: CHAIN_ID, rpc_url, oracle: Address::repeat_byte(2) };
+        (Submitter::new(config, &[chain]).unwrap(), node)
+    }
+
+    #[tokio::test]
+    async fn already_proved() {
+        let (submitter, node) = submitter(MockNode { proved: [true].into(), ..Default::default() }, 3).await;
+        assert_eq!(submitter.submit(CHAIN_ID, [1; 32], vec![]).await, Ok(None));
+        assert!(node.lock().unwrap().sent.is_empty());
+    }
+
+    #[tokio::test]
+    async fn retry_with_higher_gas_price() {
+        let node = MockNode { proved: [false].into(), statuses: [0, 1].into(), ..Default::default() };
+        let (submitter, node) = submitter(node, 1).await;
+        assert_eq!(submitter.submit(CHAIN_ID, [1; 32], vec![]).await, Ok(Some(H256::from_low_u64_be(2))));
+        // The retry replaces the first transaction with the same nonce.
+        assert_eq!(node.lock().unwrap().sent, vec![
+            (U256::from(NONCE), U256::from(GAS_PRICE)),
+            (U256::from(NONCE), U256::from(GAS_PRICE * 6 / 5)),
+        ]);
+    }
+
+    #[tokio::test]
+    async fn proved_before_retry() {
+        let node = MockNode { proved: [false, true].into(), statuses: [0].into(), ..Default::default() };
+        let (submitter, node) = submitter(node, 3).await;
+        assert_eq!(submitter.submit(CHAIN_ID, [1; 32], vec![]).await, Ok(None));
+        assert_eq!(node.lock().unwrap().sent.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_exhausted() {
+        let node = MockNode { proved: [false].into(), statuses: [0].into(), ..Default::default() };
+        let (submitter, node) = submitter(node, 0).await;
+        let error = submitter.submit(CHAIN_ID, [1; 32], vec![]).await.unwrap_err();
+        assert!(error.contains("reverted"), "{}", error);
+        assert_eq!(node.lock().unwrap().sent.len(), 1);
+        assert!(submitter.submit(1, [1; 32], vec![]).await.is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Proof request of a transaction through the server, with the local chain and mock proofs.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use web3::types::{Bytes, CallRequest, H160, U256};
+use solc_zkmod_calldata::discovery::{encode_discovery, Discovery};
+use solc_zkmod_calldata::{build_calldata, encode_witness, parse_calldata};
+use solc_zkmod_client::{Client, EstimateRequest, ProofRequest, ProofRequestData};
+use solc_zkmod_lib::keccak256;
+use solc_zkmod_lib::prover::backend::MockBackend;
+use solc_zkmod_lib::prover::prover::{ProofSystem, Prover};
+use solc_zkmod_server::batcher::{BatchPolicy, Batcher};
+use solc_zkmod_server::chain::{Chain, ChainBackend, CallResult, LocalBackend};
+use solc_zkmod_server::routes::router;
+use solc_zkmod_server::state::AppState;
+use solc_zkmod_server::store::{ApiKey, Store};
+
+const CHAIN_ID: u64 = 1;
+const CONTRACT: H160 = H160([1; 20]);
+const ORACLE: H160 = H160([2; 20]);
+const API_KEY: &str = "test-key";
+/// CALLDATASIZE PUSH0 PUSH0 CALLDATACOPY CALLDATASIZE PUSH0 RETURN
+const ECHO_CODE: [u8; 7] = [0x36, 0x5f, 0x5f, 0x37, 0x36, 0x5f, 0xf3];
+
+/// Stand-in of the generated code: reverts with the discovery until a witness entry is appended
+/// to the transaction calldata.
+fn contract_code(calldata: &[u8], discovery: &Discovery) -> Vec<u8> {
+    let without_witnesses = build_calldata(calldata, &[]).len() as u8;
+    let data = encode_discovery(discovery);
+    let len = (data.len() as u16).to_be_bytes();
+    // PUSH1 len CALLDATASIZE GT PUSH1 19 JUMPI
+    let check = [0x60, without_witnesses, 0x36, 0x11, 0x60, 19, 0x57];
+    // Reverts with the data copied from the end of the code.
+    let revert = [0x61, len[0], len[1], 0x60, 21, 0x5f, 0x39, 0x61, len[0], len[1], 0x5f, 0xfd];
+    // JUMPDEST STOP
+    [&check[..], &revert[..], &[0x5b, 0x00], &data[..]].concat()
+}
+
+#[tokio::test]
+async fn request_proof_and_witness() {
+    let calldata = vec![0xaa, 0xbb, 0xcc, 0xdd];
+    let input = vec![1, 2, 3];
+    let discovery = Discovery { code_hash: keccak256(&ECHO_CODE), input: input.clone() };
+    let chain = || {
+        let backend = LocalBackend::new();
+        backend.set_code(CONTRACT, contract_code(&calldata, &discovery));
+        backend
+    };
+
+    let prover = Arc::new(Prover::new(ProofSystem::Groth16, Box::new(MockBackend::new())));
+    let store = Arc::new(Store::in_memory());
+    store.update(|data| data.api_keys.insert(API_KEY.to_string(), ApiKey {
+        name: "test".to_string(),
+        rate_limit: None,
+        cycle_quota: None,
+        usage: Default::default(),
+    })).unwrap();
+    // The batch is closed by its first call.
+    let policy = BatchPolicy { max_calls: 1, ..Default::default() };
+    let batcher = Arc::new(Batcher::new(policy, 1, prover.clone(), None, store.clone()));
+    let chains = HashMap::from([(CHAIN_ID, Chain { backend: Box::new(chain()), oracle: ORACLE })]);
+    let state = Arc::new(AppState::new(chains, prover, batcher, store.clone()));
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = Client::new(format!("http://{}", listener.local_addr().unwrap()), API_KEY);
+    tokio::spawn(async move { axum::serve(listener, router(state, None)).await.unwrap() });
+
+    assert_eq!(client.add_bytecode(ECHO_CODE.to_vec()).await.unwrap().0, discovery.code_hash);
+    // The estimate counts against the cycle quota, without a proof request.
+    let estimate = EstimateRequest {
+        chain_id: CHAIN_ID,
+        bytecode: Bytes(ECHO_CODE.to_vec()),
+        calldata: Bytes(input.clone()),
+    };
+    let cycles = client.estimate(&estimate).await.unwrap().cycles;
+    let usage = store.read(|data| data.api_keys[API_KEY].usage.clone());
+    assert_eq!((usage.proof_requests, usage.cycles), (0, cycles));
+    // CALLER PUSH0 MSTORE, rejected the same way as its registration.
+    let forbidden = EstimateRequest { bytecode: Bytes(vec![0x33, 0x5f, 0x52]), ..estimate };
+    assert!(client.estimate(&forbidden).await.is_err());
+    let request = ProofRequest {
+        chain_id: CHAIN_ID,
+        requests: vec![ProofRequestData {
+            address: CONTRACT,
+            calldata: Bytes(calldata.clone()),
+            value: U256::zero(),
+            sender: H160([3; 20]),
+        }],
+        webhook: None,
+    };
+    let id = client.request_proof(&request).await.unwrap().id;
+    let output = tokio::time::timeout(Duration::from_secs(30), client.wait_for_witness(&id, Duration::from_millis(100)))
+        .await
+        .unwrap()
+        .unwrap();
+
+    assert_eq!(output.witnesses.len(), 1);
+    let witness = &output.witnesses[0];
+    let (parsed_calldata, entries) = parse_calldata(&witness.calldata.0).unwrap();
+    assert_eq!(parsed_calldata, calldata);
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].code_hash, discovery.code_hash);
+    assert_eq!(entries[0].input_hash, keccak256(&input));
+    assert!(!entries[0].reverted);
+    assert_eq!(entries[0].output, input);
+    // The only call of the batch, its leaf is the root.
+    assert!(entries[0].proof.is_empty());
+    assert_eq!(witness.witness.0, encode_witness(calldata.len(), &entries));
+    assert_eq!(witness.calls[0].output.0, input);
+
+    // The transaction with the witness passes the discovery.
+    let call = CallRequest {
+        from: Some(H160([3; 20])),
+        to: Some(CONTRACT),
+        data: Some(witness.calldata.clone()),
+        ..Default::default()
+    };
+    assert_eq!(chain().call(call, &[ORACLE]).await.unwrap(), CallResult::Success(vec![]));
+}
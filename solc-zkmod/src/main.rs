@@ -4,6 +4,7 @@ use std::io::Write;
 use std::str::FromStr;
 use regex::Regex;
 use sha3::Digest;
+use solc_zkmod_calldata::discovery::DISCOVERY_SELECTOR;
 
 ///
 /// Compile solidity contract into EVM bytecode, returns hex string.
@@ -68,42 +69,70 @@ impl Param {
 
 ///
 /// Returns solidity implementation of the execution verification using the `ExecutionOracle` contract.
+/// Expects the witness entries after calldata, see `solc_zkmod_calldata` for the layout.
+/// If the proven outcome is a revert, the output is the original revert data, and it's re-raised.
+/// If the execution is not proven yet, reverts with the discovery revert, see `solc_zkmod_calldata::discovery`.
 ///
-fn execution_verification_sol(code_hash: &str, inputs: Vec<Param>, outputs: Vec<Param>) -> String {
-    format!(r#"        // load needed witnesses from the scratch space after calldata
+fn execution_verification_sol(code_hash: &str, oracle: &str, inputs: Vec<Param>, outputs: Vec<Param>) -> String {
+    format!(r#"        bytes memory input = abi.encode({});
+        bytes32 inputHash = keccak256(input);
+
+        // find the witness entry of the call in the scratch space after calldata
+        bool proven;
+        uint256 status;
         bytes calldata output;
         bytes32[] calldata proof;
         assembly {{
-            output.length := calldataload(calldataload(sub(calldatasize(), 32)))
-            output.offset := add(calldataload(sub(calldatasize(), 32)), 32)
-            proof.length := calldataload(add(output.offset, output.length))
-            proof.offset := add(add(output.offset, output.length), 32)
+            let end := sub(calldatasize(), 32)
+            for {{ let entry := calldataload(end) }} and(lt(entry, end), lt(end, calldatasize())) {{}} {{
+                let outputLength := calldataload(add(entry, 96))
+                if gt(outputLength, end) {{ break }}
+                let proofLength := calldataload(add(add(entry, 128), outputLength))
+                if gt(proofLength, end) {{ break }}
+                if and(eq(calldataload(entry), 0x{}), eq(calldataload(add(entry, 32)), inputHash)) {{
+                    proven := 1
+                    status := calldataload(add(entry, 64))
+                    output.offset := add(entry, 128)
+                    output.length := outputLength
+                    proof.offset := add(add(output.offset, outputLength), 32)
+                    proof.length := proofLength
+                    break
+                }}
+                entry := add(add(add(entry, 160), outputLength), mul(proofLength, 32))
+            }}
         }}
 
         // verify execution
-        bytes memory input = abi.encode({});
-        bytes32 inputHash = keccak256(input);
-        bytes32 outputHash = keccak256(output);
-        bytes memory calldata_buffer = new bytes(100 + proof.length);
+        if (proven) {{
+            bytes memory calldata_buffer = abi.encodeWithSelector({}, bytes32(0x{}), inputHash, status, keccak256(output), proof);
+            assembly {{
+                // a call to an account without code succeeds, so the oracle must be deployed
+                proven := and(gt(extcodesize(0x{}), 0), call(gas(), 0x{}, 0, add(calldata_buffer, 32), mload(calldata_buffer), 0, 0))
+            }}
+        }}
+        if (!proven) {{
+            // not proven yet, discovery revert `FreeCallDiscovery(bytes32 codeHash, bytes input)`
+            bytes memory discovery = abi.encodeWithSelector(0x{}, bytes32(0x{}), input);
+            assembly {{
+                revert(add(discovery, 32), mload(discovery))
+            }}
+        }}
         assembly {{
-            mstore(add(calldata_buffer, 32), 0x38419e1000000000000000000000000000000000000000000000000000000000)
-            mstore(add(calldata_buffer, 36), 0x{})
-            mstore(add(calldata_buffer, 68), inputHash)
-            mstore(add(calldata_buffer, 100), outputHash)
-            calldatacopy(add(calldata_buffer, 132), add(proof.offset, 32), calldataload(proof.offset))
-            let success := call(gas(), {}, 0, add(calldata_buffer, 32), mload(calldata_buffer), 0, 0)
-            if iszero(success) {{
-                mstore(0, 0xdeadbeef)
-                mstore(32, 0x{})
-                let inputLength := mload(input)
-                mcopy(64, add(input, 32), inputLength)
-                revert(0, add(64, inputLength))
+            // proven outcome is a revert, re-raise the original revert data
+            if status {{
+                let revertData := mload(0x40)
+                calldatacopy(revertData, output.offset, output.length)
+                revert(revertData, output.length)
             }}
         }}
         return abi.decode(output, ({}));"#,
         inputs.iter().map(|arg| arg.name.clone()).collect::<Vec<_>>().as_slice().join(", "),
         code_hash,
-        EXEUCTION_ORACLE_ADDRESS,
+        VERIFY_EXECUTION_FUNCTION_SELECTOR,
+        code_hash,
+        oracle,
+        oracle,
+        hex::encode(DISCOVERY_SELECTOR),
         code_hash,
         outputs.iter().map(|arg| arg.r#type.clone()).collect::<Vec<_>>().as_slice().join(", "),
     )
@@ -130,9 +159,20 @@ contract Provable {{
     )
 }
 
-// TODO:
-const EXEUCTION_ORACLE_ADDRESS: &'static str = "0x0000000000000000000000000000000000000000000000000000000000000000";
-const VERIFY_COMPUTATION_FUNCTION_SELECTOR: &'static str = "0xTODO";
+/// `ComputationOracle.verifyExecution(bytes32,bytes32,uint8,bytes32,bytes32[])`
+const VERIFY_EXECUTION_FUNCTION_SELECTOR: &str = "0xb721dc57";
+
+///
+/// Returns the hex of the `ComputationOracle` address the generated code calls.
+/// It's required, the zero address has no code and a call to it would accept any witness.
+///
+fn oracle_address(address: &str) -> String {
+    let address = address.trim_start_matches("0x").to_lowercase();
+    let bytes = hex::decode(&address).expect("Invalid oracle address");
+    assert_eq!(bytes.len(), 20, "Invalid oracle address");
+    assert!(bytes.iter().any(|byte| *byte != 0), "Oracle address is not set");
+    address
+}
 
 ///
 /// We are pasting witnesses to the calldata, but we don't want to change the calldatasize from the contract perspective.
@@ -178,6 +218,7 @@ fn bytecode_hash(bytecode: &str) -> String {
 
 fn main() {
     let file_path = std::env::args().nth(1).unwrap();
+    let oracle = oracle_address(&std::env::args().nth(2).expect("Usage: solc-zkmod <source> <oracle address>"));
     let content = fs::read_to_string(file_path).unwrap();
     #[derive(PartialEq)]
     enum Action {
@@ -217,7 +258,7 @@ fn main() {
                 let bytecode = compile_contract(&function_wrapper, true);
                 println!("Function bytecode: {}", bytecode);
                 let bytecode_hash = bytecode_hash(&bytecode);
-                let body_to_verify = execution_verification_sol(&bytecode_hash, inputs.clone(), outputs.clone());
+                let body_to_verify = execution_verification_sol(&bytecode_hash, &oracle, inputs.clone(), outputs.clone());
                 result.push_str(&body_to_verify);
                 result.push('\n');
                 result.push_str(line);